@@ -0,0 +1,107 @@
+use std::{fs, io, os::unix::fs::OpenOptionsExt, path::Path};
+
+use thiserror::Error;
+
+use crate::sdk::common::{DefaultEthereumSigner, MessageSignerError};
+
+use super::Identity;
+
+/// Name of the file holding the generated key in the fs0x config directory.
+const GENERATED_KEY_FILE: &str = "private_key";
+
+#[derive(Debug, Error)]
+pub enum IdentityError {
+    #[error("failed to load signing key: {0}")]
+    Signer(#[from] MessageSignerError),
+
+    #[error("environment variable {0} is not set")]
+    MissingEnv(String),
+
+    #[error("failed to access key file: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("no config directory found to store the generated key")]
+    NoConfigDir,
+}
+
+/// Builds the signer identifying the mount on the Aleph network.
+///
+/// The key comes, by order of precedence, from the encrypted keystore given
+/// with `--keystore`, the environment variable named by `--private-key-env`,
+/// or a key generated on first use and stored in the user's config directory.
+pub fn load_signer(args: &Identity) -> Result<DefaultEthereumSigner, IdentityError> {
+    if let Some(keystore) = &args.keystore {
+        let password = read_env(&args.keystore_password_env)?;
+
+        return Ok(DefaultEthereumSigner::from_keystore(Path::new(keystore), &password)?);
+    }
+
+    if let Some(private_key_env) = &args.private_key_env {
+        let private_key = read_env(private_key_env)?;
+
+        return Ok(DefaultEthereumSigner::new(private_key)?);
+    }
+
+    let config_dir = dirs::config_dir().ok_or(IdentityError::NoConfigDir)?;
+
+    load_or_generate(&config_dir.join("fs0x").join(GENERATED_KEY_FILE))
+}
+
+fn read_env(name: &str) -> Result<String, IdentityError> {
+    std::env::var(name).map_err(|_| IdentityError::MissingEnv(name.to_string()))
+}
+
+/// Loads the key stored at `path`, generating and storing a new one if the
+/// file does not exist yet.
+fn load_or_generate(path: &Path) -> Result<DefaultEthereumSigner, IdentityError> {
+    if path.exists() {
+        let private_key = fs::read_to_string(path)?;
+
+        return Ok(DefaultEthereumSigner::new(private_key.trim().to_string())?);
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let signer = DefaultEthereumSigner::random();
+
+    // The key grants full control over the account, keep it private.
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+        .and_then(|mut file| io::Write::write_all(&mut file, signer.private_key().as_bytes()))?;
+
+    Ok(signer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdk::common::MessageSigner;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn it_generates_then_reloads_the_key() {
+        let dir = std::env::temp_dir().join(format!("fs0x-identity-{}", std::process::id()));
+        let path = dir.join(GENERATED_KEY_FILE);
+
+        let generated = load_or_generate(&path).unwrap();
+        let reloaded = load_or_generate(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(generated.get_address(), reloaded.get_address());
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn it_fails_on_missing_environment_variable() {
+        let res = read_env("FS0X_TEST_UNSET_VARIABLE");
+
+        assert!(matches!(res, Err(IdentityError::MissingEnv(_))));
+    }
+}
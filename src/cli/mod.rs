@@ -1,17 +1,157 @@
-use clap::Parser;
+mod identity;
 
+use clap::{Parser, Subcommand};
+
+pub use identity::load_signer;
+
+/// Mount a filesystem mirrored on the Aleph network
 #[derive(Parser, Debug)]
-#[clap(version, about, long_about = None)]
+#[clap(version, about, long_about = None, args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct Args {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
     /// Mount point path
     #[clap(required = true)]
-    pub mount_point: String,
+    pub mount_point: Option<String>,
 
     /// Allow root user to access filesystem
     #[clap(long, short)]
     pub root_access: bool,
 
+    #[clap(flatten)]
+    pub identity: Identity,
+
+    /// Capacity reported for the mount in bytes, defaults to the storage
+    /// allowance of the account's ALEPH balance
+    #[clap(long)]
+    pub quota: Option<u64>,
+
+    /// Directory file contents are cached in, defaults to the fs0x directory
+    /// of the user's cache directory
+    #[clap(long)]
+    pub cache_dir: Option<String>,
+
+    /// Size limit of the content cache in bytes, 0 disables the cache
+    #[clap(long, default_value_t = 1024 * 1024 * 1024)]
+    pub cache_size: u64,
+
+    /// Directory file contents that failed to upload are kept in until they
+    /// are, defaults to the fs0x directory of the user's data directory
+    #[clap(long)]
+    pub spool_dir: Option<String>,
+
+    /// Interval in seconds between two fetches of the tree published by peers
+    #[clap(long, default_value_t = 5)]
+    pub sync_interval: u64,
+}
+
+/// Options selecting the account and the FSTree a command works on
+#[derive(clap::Args, Debug)]
+pub struct Identity {
     /// Unique ID for FSTree identification
-    #[clap(long, short)]
-    pub id: String,
-}
\ No newline at end of file
+    #[clap(long, short, required = true)]
+    pub id: Option<String>,
+
+    /// Encrypted JSON keystore holding the signing key
+    #[clap(long, conflicts_with = "private_key_env")]
+    pub keystore: Option<String>,
+
+    /// Environment variable holding the keystore password
+    #[clap(long, default_value = "FS0X_KEYSTORE_PASSWORD")]
+    pub keystore_password_env: String,
+
+    /// Environment variable holding the hex encoded signing key
+    #[clap(long)]
+    pub private_key_env: Option<String>,
+
+    /// Encrypt the tree and file contents with a key derived from the
+    /// signing key
+    #[clap(long)]
+    pub encrypt: bool,
+
+    /// Address of another account allowed to change the tree, can be repeated
+    #[clap(long = "allow-sender", value_name = "ADDRESS")]
+    pub allowed_senders: Vec<String>,
+}
+
+/// Commands run instead of mounting the filesystem
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Show the tree published by the peers and the conflicts between them
+    Status {
+        #[clap(flatten)]
+        identity: Identity,
+    },
+    /// Roll the tree back to a past snapshot, published as its new version
+    Restore {
+        #[clap(flatten)]
+        identity: Identity,
+
+        /// Hash of the snapshot post, or a date such as `2024-05-01T12:00:00Z`
+        /// or a UNIX timestamp to restore the latest snapshot posted until then
+        point: String,
+
+        /// Only show the changes the restore would make
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Show the changes between two versions of the tree
+    Diff {
+        #[clap(flatten)]
+        identity: Identity,
+
+        /// Hash of the snapshot post or date of the old version
+        from: String,
+
+        /// Hash of the snapshot post or date of the new version, defaults to
+        /// the latest version
+        to: Option<String>,
+
+        /// Compare the old version with a local directory instead
+        #[clap(long, value_name = "DIR", conflicts_with = "to")]
+        local: Option<String>,
+    },
+    /// Publish a local directory as the new version of the tree, without
+    /// mounting it
+    Push {
+        #[clap(flatten)]
+        identity: Identity,
+
+        /// Local directory to publish
+        dir: String,
+
+        /// Only show the changes the push would make
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Write the latest version of the tree to a local directory, without
+    /// mounting it
+    Pull {
+        #[clap(flatten)]
+        identity: Identity,
+
+        /// Local directory to write the tree to, created if needed
+        dir: String,
+
+        /// Remove the local entries missing from the tree
+        #[clap(long)]
+        delete: bool,
+
+        /// Only show the changes the pull would make
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Publish a local directory every time it changes, without mounting it
+    Watch {
+        #[clap(flatten)]
+        identity: Identity,
+
+        /// Local directory to publish
+        dir: String,
+
+        /// Time in milliseconds without changes before they are published
+        #[clap(long, default_value_t = 1000)]
+        debounce: u64,
+    },
+}
@@ -0,0 +1,279 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use sha2::{Digest, Sha256};
+
+/// Counters of the accesses to a `ContentCache`
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    /// Cached contents that did not match their hash and were dropped
+    pub invalid: u64,
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} hits, {} misses, {} evictions, {} invalid", self.hits, self.misses, self.evictions, self.invalid)
+    }
+}
+
+/// A content stored in the cache directory
+struct Cached {
+    size: u64,
+    /// Position of the last access among the accesses to the cache
+    used: u64,
+}
+
+/// Local copy of the contents downloaded from the Aleph storage
+///
+/// Contents are stored as files named by their hash in the cache directory,
+/// so that they survive re-mounts. Once the cache grows over its limit, the
+/// least recently used contents are evicted.
+pub struct ContentCache {
+    dir: PathBuf,
+    limit: u64,
+    used_bytes: u64,
+    contents: HashMap<String, Cached>,
+    /// Hashes of the cached contents by last access, oldest first
+    recency: BTreeMap<u64, String>,
+    clock: u64,
+    stats: CacheStats,
+}
+
+impl ContentCache {
+    /// Open the cache stored in `dir`, creating the directory if needed
+    ///
+    /// Contents cached by a previous mount are kept, ordered by the time they
+    /// were last used.
+    pub fn open(dir: &Path, limit: u64) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        let mut found = Vec::new();
+        for dir_entry in fs::read_dir(dir)? {
+            let dir_entry = dir_entry?;
+            let metadata = dir_entry.metadata()?;
+            let name = dir_entry.file_name().to_string_lossy().to_string();
+
+            // Leftovers of contents being written when the last mount stopped.
+            if name.ends_with(".tmp") {
+                let _ = fs::remove_file(dir_entry.path());
+                continue;
+            }
+            if !metadata.is_file() || !is_cache_key(&name) {
+                continue;
+            }
+            found.push((metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH), name, metadata.len()));
+        }
+        found.sort();
+
+        let mut cache = Self {
+            dir: dir.to_path_buf(),
+            limit,
+            used_bytes: 0,
+            contents: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+            stats: CacheStats::default(),
+        };
+        for (_, hash, size) in found {
+            cache.track(hash, size);
+        }
+        cache.evict();
+
+        Ok(cache)
+    }
+
+    /// Get a cached content by hash
+    ///
+    /// A content that no longer matches its hash is dropped and counted as a
+    /// miss.
+    pub fn get(&mut self, hash: &str) -> Option<Vec<u8>> {
+        if !self.contents.contains_key(hash) {
+            self.stats.misses += 1;
+            return None;
+        }
+
+        let data = match fs::read(self.path(hash)) {
+            Ok(data) if matches_hash(hash, &data) => data,
+            Ok(_) => {
+                self.stats.invalid += 1;
+                self.stats.misses += 1;
+                self.remove(hash);
+                return None;
+            },
+            Err(_) => {
+                self.stats.misses += 1;
+                self.untrack(hash);
+                return None;
+            }
+        };
+
+        self.stats.hits += 1;
+        self.touch(hash);
+
+        Some(data)
+    }
+
+    /// Store a content under its hash, evicting older contents if needed
+    ///
+    /// Contents not matching their hash or larger than the cache are not
+    /// stored.
+    pub fn insert(&mut self, hash: &str, data: &[u8]) {
+        let size = data.len() as u64;
+
+        if size > self.limit || !is_cache_key(hash) || !matches_hash(hash, data) {
+            return;
+        }
+        if self.contents.contains_key(hash) {
+            self.touch(hash);
+            return;
+        }
+
+        // Write to a temporary file first so that a crash never leaves a
+        // partial content behind under its hash.
+        let tmp = self.dir.join(format!(".{}.tmp", hash));
+        if let Err(e) = fs::write(&tmp, data).and_then(|_| fs::rename(&tmp, self.path(hash))) {
+            eprintln!("failed to cache content {}: {}", hash, e);
+            let _ = fs::remove_file(&tmp);
+            return;
+        }
+
+        self.track(hash.to_string(), size);
+        self.evict();
+    }
+
+    /// Counters of the accesses to the cache
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Bytes taken by the cached contents
+    pub fn used_bytes(&self) -> u64 {
+        self.used_bytes
+    }
+
+    fn path(&self, hash: &str) -> PathBuf {
+        self.dir.join(hash)
+    }
+
+    fn track(&mut self, hash: String, size: u64) {
+        self.clock += 1;
+        self.used_bytes += size;
+        self.recency.insert(self.clock, hash.clone());
+        self.contents.insert(hash, Cached { size, used: self.clock });
+    }
+
+    fn untrack(&mut self, hash: &str) {
+        if let Some(cached) = self.contents.remove(hash) {
+            self.used_bytes -= cached.size;
+            self.recency.remove(&cached.used);
+        }
+    }
+
+    /// Mark a content as the most recently used
+    fn touch(&mut self, hash: &str) {
+        self.clock += 1;
+
+        if let Some(cached) = self.contents.get_mut(hash) {
+            self.recency.remove(&cached.used);
+            self.recency.insert(self.clock, hash.to_string());
+            cached.used = self.clock;
+        }
+
+        // The modification time orders the contents on the next mount.
+        if let Ok(file) = fs::File::options().append(true).open(self.path(hash)) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+    }
+
+    fn remove(&mut self, hash: &str) {
+        self.untrack(hash);
+        let _ = fs::remove_file(self.path(hash));
+    }
+
+    /// Remove the least recently used contents until the cache fits its limit
+    fn evict(&mut self) {
+        while self.used_bytes > self.limit {
+            let hash = match self.recency.first_key_value() {
+                Some((_, hash)) => hash.clone(),
+                None => break,
+            };
+
+            self.remove(&hash);
+            self.stats.evictions += 1;
+        }
+    }
+}
+
+/// Whether a name can be a file of the cache directory
+fn is_cache_key(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Whether data matches the hash it is stored under on the Aleph storage
+///
+/// Storage hashes are the SHA-256 of the content, other hashes such as IPFS
+/// CIDs are not checked.
+pub fn matches_hash(hash: &str, data: &[u8]) -> bool {
+    let is_sha256 = hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit());
+
+    !is_sha256 || hex::encode(Sha256::digest(data)).eq_ignore_ascii_case(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(data: &[u8]) -> String {
+        hex::encode(Sha256::digest(data))
+    }
+
+    #[test]
+    fn it_evicts_the_least_recently_used_contents() {
+        let dir = std::env::temp_dir().join(format!("fs0x-cache-lru-{}", std::process::id()));
+        let mut cache = ContentCache::open(&dir, 8).unwrap();
+
+        cache.insert(&hash(b"aaaa"), b"aaaa");
+        cache.insert(&hash(b"bbbb"), b"bbbb");
+        assert_eq!(cache.get(&hash(b"aaaa")), Some(b"aaaa".to_vec()));
+
+        cache.insert(&hash(b"cccc"), b"cccc");
+        assert_eq!(cache.get(&hash(b"bbbb")), None);
+        assert_eq!(cache.used_bytes(), 8);
+
+        // Contents survive a re-mount.
+        let mut reopened = ContentCache::open(&dir, 8).unwrap();
+        let found = reopened.get(&hash(b"cccc"));
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1, evictions: 1, invalid: 0 });
+        assert_eq!(found, Some(b"cccc".to_vec()));
+        assert_eq!(reopened.used_bytes(), 8);
+    }
+
+    #[test]
+    fn it_drops_contents_not_matching_their_hash() {
+        let dir = std::env::temp_dir().join(format!("fs0x-cache-hash-{}", std::process::id()));
+        let mut cache = ContentCache::open(&dir, 1024).unwrap();
+
+        cache.insert(&hash(b"other"), b"content");
+        assert_eq!(cache.used_bytes(), 0);
+
+        cache.insert(&hash(b"content"), b"content");
+        fs::write(dir.join(hash(b"content")), b"tampered").unwrap();
+        let found = cache.get(&hash(b"content"));
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(found, None);
+        assert_eq!(cache.stats().invalid, 1);
+        assert_eq!(cache.used_bytes(), 0);
+    }
+}
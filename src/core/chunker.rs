@@ -0,0 +1,133 @@
+use sha2::{Digest, Sha256};
+
+use crate::crypto::Cipher;
+use crate::mirroring::Chunk;
+
+/// Smallest chunk cut, but for the last chunk of a content
+const MIN_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Largest chunk cut, whatever the content
+const MAX_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Bits of the rolling hash that must be zero to cut a chunk, averaging
+/// chunks of 1 MiB past the minimum size
+const CUT_MASK: u64 = (1 << 20) - 1;
+
+/// Random values the rolling hash mixes in for each byte
+///
+/// They are generated with SplitMix64 from a fixed seed, peers must cut the
+/// same content at the same places to share its chunks.
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x6673_3078_6765_6172;
+    let mut i = 0;
+
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+
+    table
+}
+
+/// Split a content into chunks cut where its bytes match a pattern
+///
+/// Cuts only depend on the bytes right before them, so that an insertion or
+/// an append only changes the chunks around it and identical content cuts
+/// the same in every file.
+pub fn split(data: &[u8]) -> Vec<&[u8]> {
+    let mut chunks = Vec::new();
+    let mut rest = data;
+
+    while !rest.is_empty() {
+        let (chunk, remaining) = rest.split_at(cut_point(rest));
+        chunks.push(chunk);
+        rest = remaining;
+    }
+
+    chunks
+}
+
+/// Split a content into the chunks stored for it, with their stored bytes
+///
+/// Encrypted chunks are sealed the same way every time, so that identical
+/// ones keep the same hash.
+pub fn seal(data: &[u8], cipher: Option<&Cipher>) -> Vec<(Chunk, Vec<u8>)> {
+    split(data).into_iter().map(|piece| {
+        let sealed = match cipher {
+            Some(cipher) => cipher.seal_convergent(piece),
+            None => piece.to_vec(),
+        };
+        let chunk = Chunk {
+            hash: hex::encode(Sha256::digest(&sealed)),
+            size: piece.len() as u64,
+        };
+
+        (chunk, sealed)
+    }).collect()
+}
+
+/// Length of the next chunk of a content
+fn cut_point(data: &[u8]) -> usize {
+    if data.len() <= MIN_CHUNK_SIZE {
+        return data.len();
+    }
+
+    let end = data.len().min(MAX_CHUNK_SIZE);
+    let mut hash: u64 = 0;
+
+    for (i, byte) in data.iter().enumerate().take(end).skip(MIN_CHUNK_SIZE) {
+        hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+        if hash & CUT_MASK == 0 {
+            return i + 1;
+        }
+    }
+
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content(size: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+
+        (0..size).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        }).collect()
+    }
+
+    #[test]
+    fn it_keeps_small_contents_whole() {
+        assert_eq!(split(b"small"), vec![b"small".as_slice()]);
+        assert!(split(b"").is_empty());
+    }
+
+    #[test]
+    fn it_only_changes_the_chunks_around_an_edit() {
+        let data = content(8 * 1024 * 1024, 42);
+        let chunks = split(&data);
+
+        assert!(chunks.len() > 2);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= MAX_CHUNK_SIZE));
+        assert_eq!(chunks.concat(), data);
+
+        let mut edited = data[..100].to_vec();
+        edited.extend_from_slice(b"inserted");
+        edited.extend_from_slice(&data[100..]);
+        let edited_chunks = split(&edited);
+
+        assert_ne!(edited_chunks[0], chunks[0]);
+        assert_eq!(edited_chunks[1..], chunks[1..]);
+    }
+}
@@ -0,0 +1,136 @@
+use super::inode::Inode;
+use crate::mirroring::Chunk;
+
+/// State of a file opened through the filesystem
+pub struct FileHandle {
+    /// Inode of the opened file
+    pub inode: Inode,
+    /// Content of the file, loaded from the Aleph storage on first access
+    pub data: Option<Vec<u8>>,
+    /// Chunks of the stored content `data` was loaded from or last uploaded as
+    pub content: Vec<Chunk>,
+    /// Whether the content has been modified since it was last uploaded
+    pub dirty: bool,
+    /// Whether the handle was released before its content could be uploaded
+    pub released: bool,
+}
+
+impl FileHandle {
+    /// Create a new handle on a file inode
+    pub fn new(inode: Inode, data: Option<Vec<u8>>, dirty: bool) -> Self {
+        Self {
+            inode,
+            data,
+            content: vec![],
+            dirty,
+            released: false,
+        }
+    }
+
+    /// Write data at the given offset, growing the buffer if needed
+    pub fn write(&mut self, offset: usize, data: &[u8]) -> u64 {
+        let buffer = self.data.get_or_insert_with(Vec::new);
+        let end = offset + data.len();
+
+        if buffer.len() < end {
+            buffer.resize(end, 0);
+        }
+        buffer[offset..end].copy_from_slice(data);
+        self.dirty = true;
+
+        buffer.len() as u64
+    }
+
+    /// Resize the content, padding it with zeros when growing
+    pub fn truncate(&mut self, size: usize) {
+        self.data.get_or_insert_with(Vec::new).resize(size, 0);
+        self.dirty = true;
+    }
+
+    /// Drop the loaded content if it no longer matches the stored one
+    ///
+    /// Dirty buffers are kept, as they hold writes not uploaded yet.
+    pub fn invalidate(&mut self, content: &[Chunk]) {
+        if !self.dirty && self.content != content {
+            self.data = None;
+            self.content = vec![];
+        }
+    }
+
+    /// Read at most `size` bytes from the given offset
+    pub fn read(&self, offset: usize, size: usize) -> &[u8] {
+        match &self.data {
+            Some(buffer) if offset < buffer.len() => {
+                let end = std::cmp::min(offset + size, buffer.len());
+                &buffer[offset..end]
+            },
+            _ => &[],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_writes_past_the_end_of_the_buffer() {
+        let mut handle = FileHandle::new(2, Some(b"Hello".to_vec()), false);
+
+        let size = handle.write(7, b"fs0x");
+
+        assert_eq!(size, 11);
+        assert!(handle.dirty);
+        assert_eq!(handle.data.unwrap(), b"Hello\0\0fs0x");
+    }
+
+    #[test]
+    fn it_truncates_and_extends_the_buffer() {
+        let mut handle = FileHandle::new(2, Some(b"Hello fs0x".to_vec()), false);
+
+        handle.truncate(5);
+        assert!(handle.dirty);
+        assert_eq!(handle.data.as_deref(), Some(&b"Hello"[..]));
+
+        handle.truncate(7);
+        assert_eq!(handle.data.unwrap(), b"Hello\0\0");
+    }
+
+    fn chunk(hash: &str) -> Vec<Chunk> {
+        vec![Chunk {
+            hash: hash.to_string(),
+            size: 5,
+        }]
+    }
+
+    #[test]
+    fn it_invalidates_clean_content_with_another_hash() {
+        let mut handle = FileHandle::new(2, Some(b"Hello".to_vec()), false);
+        handle.content = chunk("old");
+
+        handle.invalidate(&chunk("old"));
+        assert!(handle.data.is_some());
+
+        handle.invalidate(&chunk("new"));
+        assert!(handle.data.is_none());
+    }
+
+    #[test]
+    fn it_keeps_dirty_content_on_invalidation() {
+        let mut handle = FileHandle::new(2, Some(b"Hello".to_vec()), false);
+        handle.write(0, b"J");
+
+        handle.invalidate(&chunk("new"));
+
+        assert_eq!(handle.data.unwrap(), b"Jello");
+    }
+
+    #[test]
+    fn it_reads_a_range_of_the_buffer() {
+        let handle = FileHandle::new(2, Some(b"Hello fs0x".to_vec()), false);
+
+        assert_eq!(handle.read(6, 2), b"fs");
+        assert_eq!(handle.read(6, 100), b"fs0x");
+        assert_eq!(handle.read(100, 2), b"");
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+use super::inode::Inode;
+
+/// Lookup tables from the names and paths of the local inodes to the inodes
+///
+/// Every name of an inode, hard links included, is indexed both by its
+/// parent directory and by its full path. Paths end with a `/`, the root
+/// being `/`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InodeIndex {
+    names: BTreeMap<(Inode, String), Inode>,
+    paths: BTreeMap<String, Inode>,
+}
+
+impl InodeIndex {
+    pub fn new() -> Self {
+        Self {
+            names: BTreeMap::new(),
+            paths: BTreeMap::from([("/".to_string(), fuser::FUSE_ROOT_ID)]),
+        }
+    }
+
+    /// Index a name of an inode in a parent directory stored at `path`
+    pub fn insert(&mut self, parent: Inode, name: &str, path: String, inode: Inode) {
+        self.names.insert((parent, name.to_string()), inode);
+        self.paths.insert(path, inode);
+    }
+
+    /// Drop a name of an inode stored at `path`
+    pub fn remove(&mut self, parent: Inode, name: &str, path: &str) {
+        self.names.remove(&(parent, name.to_string()));
+        self.paths.remove(path);
+    }
+
+    /// Inode of a name in a parent directory
+    pub fn lookup(&self, parent: Inode, name: &str) -> Option<Inode> {
+        self.names.get(&(parent, name.to_string())).copied()
+    }
+
+    /// Inode stored at a path
+    pub fn resolve(&self, path: &str) -> Option<Inode> {
+        self.paths.get(path).copied()
+    }
+
+    /// Move a name to a new parent directory and name, along with the paths
+    /// below it
+    pub fn rename(&mut self, from: (Inode, &str, &str), to: (Inode, &str, String)) {
+        let ((parent, name, from_path), (newparent, newname, to_path)) = (from, to);
+
+        if let Some(inode) = self.names.remove(&(parent, name.to_string())) {
+            self.names.insert((newparent, newname.to_string()), inode);
+        }
+
+        for (path, inode) in self.take_subtree(from_path) {
+            self.paths.insert(format!("{}{}", to_path, &path[from_path.len()..]), inode);
+        }
+    }
+
+    /// Swap two names of different inodes, along with the paths below them
+    pub fn exchange(&mut self, a: (Inode, &str, &str), b: (Inode, &str, &str)) {
+        let ((a_parent, a_name, a_path), (b_parent, b_name, b_path)) = (a, b);
+        let (a_key, b_key) = ((a_parent, a_name.to_string()), (b_parent, b_name.to_string()));
+
+        if let (Some(a_inode), Some(b_inode)) = (self.names.get(&a_key).copied(), self.names.get(&b_key).copied()) {
+            self.names.insert(a_key, b_inode);
+            self.names.insert(b_key, a_inode);
+        }
+
+        let a_subtree = self.take_subtree(a_path);
+        let b_subtree = self.take_subtree(b_path);
+
+        for (path, inode) in a_subtree {
+            self.paths.insert(format!("{}{}", b_path, &path[a_path.len()..]), inode);
+        }
+        for (path, inode) in b_subtree {
+            self.paths.insert(format!("{}{}", a_path, &path[b_path.len()..]), inode);
+        }
+    }
+
+    /// Remove and return a path along with the paths below it
+    fn take_subtree(&mut self, path: &str) -> Vec<(String, Inode)> {
+        let subtree: Vec<(String, Inode)> = self.paths
+            .range::<str, _>((Bound::Included(path), Bound::Unbounded))
+            .take_while(|(other, _)| other.starts_with(path))
+            .map(|(other, inode)| (other.clone(), *inode))
+            .collect();
+
+        for (other, _) in &subtree {
+            self.paths.remove(other);
+        }
+
+        subtree
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_index() -> InodeIndex {
+        let mut index = InodeIndex::new();
+
+        index.insert(1, "docs", "/docs/".to_string(), 2);
+        index.insert(2, "a.txt", "/docs/a.txt/".to_string(), 3);
+        index.insert(1, "docs.txt", "/docs.txt/".to_string(), 4);
+        index.insert(1, "b.txt", "/b.txt/".to_string(), 3);
+
+        index
+    }
+
+    #[test]
+    fn it_looks_up_names_and_paths() {
+        let mut index = test_index();
+
+        assert_eq!(index.resolve("/"), Some(fuser::FUSE_ROOT_ID));
+        assert_eq!(index.lookup(2, "a.txt"), Some(3));
+        assert_eq!(index.resolve("/b.txt/"), Some(3));
+
+        index.remove(1, "b.txt", "/b.txt/");
+
+        assert_eq!(index.lookup(1, "b.txt"), None);
+        assert_eq!(index.resolve("/b.txt/"), None);
+        assert_eq!(index.resolve("/docs/a.txt/"), Some(3));
+    }
+
+    #[test]
+    fn it_renames_subtrees() {
+        let mut index = test_index();
+
+        index.rename((1, "docs", "/docs/"), (4, "notes", "/docs.txt/notes/".to_string()));
+
+        assert_eq!(index.lookup(1, "docs"), None);
+        assert_eq!(index.lookup(4, "notes"), Some(2));
+        assert_eq!(index.resolve("/docs.txt/notes/a.txt/"), Some(3));
+        assert_eq!(index.resolve("/docs/a.txt/"), None);
+        // Siblings sharing the prefix without the separator stay in place.
+        assert_eq!(index.resolve("/docs.txt/"), Some(4));
+    }
+
+    #[test]
+    fn it_exchanges_subtrees() {
+        let mut index = test_index();
+
+        index.exchange((1, "docs", "/docs/"), (1, "b.txt", "/b.txt/"));
+
+        assert_eq!(index.lookup(1, "docs"), Some(3));
+        assert_eq!(index.lookup(1, "b.txt"), Some(2));
+        assert_eq!(index.resolve("/b.txt/a.txt/"), Some(3));
+        assert_eq!(index.resolve("/docs/"), Some(3));
+        assert_eq!(index.resolve("/docs/a.txt/"), None);
+    }
+}
@@ -2,7 +2,7 @@ use serde::{Serialize, Deserialize};
 use std::collections::BTreeMap;
 use std::time::SystemTime;
 
-const BLOCK_SIZE: u64 = 512;
+pub const BLOCK_SIZE: u64 = 512;
 
 pub type Inode = u64;
 
@@ -22,6 +22,93 @@ pub struct InodeAttributes {
     pub uid: u32,
     pub gid: u32,
     pub xattrs: BTreeMap<Vec<u8>, Vec<u8>>,
+    /// Parent directory and name of the hard links made to the file, on top
+    /// of `pinode` and `fname`
+    pub links: Vec<(Inode, String)>,
+}
+
+impl InodeAttributes {
+    /// Names of the inode in a directory, the primary one first
+    pub fn names_in(&self, parent: Inode) -> Vec<&str> {
+        let primary = match self.pinode == Some(parent) {
+            true => Some(self.fname.as_str()),
+            false => None,
+        };
+
+        primary.into_iter()
+            .chain(self.links.iter().filter(|(p, _)| *p == parent).map(|(_, name)| name.as_str()))
+            .collect()
+    }
+
+    /// Add a hard link to the inode
+    pub fn add_link(&mut self, parent: Inode, name: String) {
+        self.links.push((parent, name));
+        self.hardlinks += 1;
+    }
+
+    /// Replace one of the names of the inode
+    pub fn rename(&mut self, from: (Inode, &str), to: (Inode, String)) {
+        if self.pinode == Some(from.0) && self.fname == from.1 {
+            (self.pinode, self.fname) = (Some(to.0), to.1);
+        } else if let Some(link) = self.links.iter_mut().find(|(p, name)| *p == from.0 && name == from.1) {
+            *link = to;
+        }
+    }
+
+    /// Drop one of the names of the inode, a hard link takes over when the
+    /// primary name goes
+    ///
+    /// Returns whether the inode is still linked somewhere.
+    pub fn unlink(&mut self, parent: Inode, name: &str) -> bool {
+        if self.pinode == Some(parent) && self.fname == name {
+            match self.links.is_empty() {
+                true => {
+                    return false;
+                },
+                false => {
+                    let (pinode, fname) = self.links.remove(0);
+                    (self.pinode, self.fname) = (Some(pinode), fname);
+                }
+            }
+        } else {
+            self.links.retain(|(p, n)| !(*p == parent && n == name));
+        }
+
+        self.hardlinks = 1 + self.links.len() as u32;
+        true
+    }
+
+    /// Whether a user may access the inode with a mask of `libc::R_OK`,
+    /// `libc::W_OK` and `libc::X_OK` flags
+    pub fn check_access(&self, uid: u32, gid: u32, mask: i32) -> bool {
+        let mode = self.mode as i32;
+
+        // Root is only denied execution when nobody is allowed to.
+        if uid == 0 {
+            return mask & libc::X_OK == 0 || mode & 0o111 != 0;
+        }
+
+        let perms = match (uid == self.uid, gid == self.gid) {
+            (true, _) => mode >> 6,
+            (false, true) => mode >> 3,
+            _ => mode,
+        } & 0o7;
+
+        mask & perms == mask
+    }
+
+    /// Clear the setuid and setgid bits, as done when a file changes owner
+    /// or content
+    ///
+    /// The setgid bit is kept without group execution, where it marks
+    /// mandatory locking instead.
+    pub fn clear_suid_sgid(&mut self) {
+        self.mode &= !(libc::S_ISUID as u16);
+
+        if self.mode & libc::S_IXGRP as u16 != 0 {
+            self.mode &= !(libc::S_ISGID as u16);
+        }
+    }
 }
 
 impl From<&InodeAttributes> for fuser::FileAttr {
@@ -29,7 +116,7 @@ impl From<&InodeAttributes> for fuser::FileAttr {
         fuser::FileAttr {
             ino: attrs.inode,
             size: attrs.size,
-            blocks: (attrs.size + BLOCK_SIZE - 1) / BLOCK_SIZE,
+            blocks: attrs.size.div_ceil(BLOCK_SIZE),
             atime: attrs.last_accessed,
             mtime: attrs.last_modified,
             ctime: attrs.last_metadata_changed,
@@ -50,6 +137,7 @@ impl From<&InodeAttributes> for fuser::FileAttr {
 pub enum FileKind {
     File,
     Directory(Vec<Inode>),
+    Symlink(String),
 }
 
 impl From<FileKind> for fuser::FileType {
@@ -57,6 +145,73 @@ impl From<FileKind> for fuser::FileType {
         match kind {
             FileKind::File => fuser::FileType::RegularFile,
             FileKind::Directory(_) => fuser::FileType::Directory,
+            FileKind::Symlink(_) => fuser::FileType::Symlink,
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attributes(mode: u16) -> InodeAttributes {
+        InodeAttributes {
+            inode: 2,
+            pinode: Some(1),
+            fname: "file.txt".to_string(),
+            open_file_handles: 0,
+            size: 0,
+            last_accessed: SystemTime::now(),
+            last_modified: SystemTime::now(),
+            last_metadata_changed: SystemTime::now(),
+            kind: FileKind::File,
+            mode,
+            hardlinks: 1,
+            uid: 1000,
+            gid: 1000,
+            xattrs: Default::default(),
+            links: vec![],
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn it_checks_access_by_owner_group_and_others() {
+        let attr = attributes(0o640);
+
+        assert!(attr.check_access(1000, 1000, libc::R_OK | libc::W_OK));
+        assert!(attr.check_access(1001, 1000, libc::R_OK));
+        assert!(!attr.check_access(1001, 1000, libc::W_OK));
+        assert!(!attr.check_access(1001, 1001, libc::R_OK));
+        assert!(attr.check_access(0, 0, libc::R_OK | libc::W_OK));
+        assert!(!attr.check_access(0, 0, libc::X_OK));
+    }
+
+    #[test]
+    fn it_keeps_track_of_hard_links() {
+        let mut attr = attributes(0o644);
+        attr.add_link(3, "copy.txt".to_string());
+        attr.add_link(1, "other.txt".to_string());
+
+        assert_eq!(attr.hardlinks, 3);
+        assert_eq!(attr.names_in(1), vec!["file.txt", "other.txt"]);
+
+        attr.rename((1, "other.txt"), (3, "moved.txt".to_string()));
+        assert_eq!(attr.names_in(3), vec!["copy.txt", "moved.txt"]);
+
+        assert!(attr.unlink(1, "file.txt"));
+        assert_eq!((attr.pinode, attr.fname.as_str(), attr.hardlinks), (Some(3), "copy.txt", 2));
+
+        assert!(attr.unlink(3, "moved.txt"));
+        assert!(!attr.unlink(3, "copy.txt"));
+    }
+
+    #[test]
+    fn it_clears_suid_and_sgid() {
+        let mut attr = attributes(0o6755);
+        attr.clear_suid_sgid();
+        assert_eq!(attr.mode, 0o755);
+
+        let mut attr = attributes(0o2644);
+        attr.clear_suid_sgid();
+        assert_eq!(attr.mode, 0o2644);
+    }
+}
@@ -0,0 +1,294 @@
+use libc::c_int;
+use std::fs;
+use std::io;
+use std::os::unix::fs::{lchown, symlink, PermissionsExt};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use super::chunker;
+use super::sync::fetch_fs_tree;
+use super::FS0X;
+use crate::crypto::Cipher;
+use crate::mirroring::{self, Change, Chunk, Diff, Entry, FSTree, Operation};
+use crate::sdk::post::v0::PostSDKV0Error;
+
+#[derive(Debug, Error)]
+pub enum LocalError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+
+    #[error("no tree found on {0}")]
+    NotFound(String),
+
+    #[error("failed to fetch the tree of {0}: {1}")]
+    Fetch(String, PostSDKV0Error),
+
+    #[error("failed to upload the content of {0} (error {1})")]
+    Upload(String, c_int),
+
+    #[error("failed to download the content of {0} (error {1})")]
+    Download(String, c_int),
+
+    #[error("failed to publish the changes (error {0})")]
+    Publish(c_int),
+}
+
+/// Read a local directory into a FSTree, with the content hashes its files
+/// would be stored under
+pub fn read_local_tree(dir: &Path, cipher: Option<&Cipher>) -> io::Result<FSTree> {
+    read_changed_tree(dir, cipher, &FSTree::new("/".to_string()))
+}
+
+/// Read a local directory into a FSTree, only hashing the content of the
+/// files whose size or modification time differ in `known`
+fn read_changed_tree(dir: &Path, cipher: Option<&Cipher>, known: &FSTree) -> io::Result<FSTree> {
+    mirroring::read_local_tree(dir, changed_content(cipher, known))
+}
+
+/// Content of the local files, only hashed for the files whose size or
+/// modification time differ in `known`
+pub(super) fn changed_content<'a>(cipher: Option<&'a Cipher>, known: &'a FSTree) -> impl FnMut(&Path, &mirroring::File) -> io::Result<Vec<Chunk>> + 'a {
+    move |path, file| {
+        if let Some(Entry::File(known)) = known.get_entry(&file.path) {
+            if known.size == file.size && known.modified_at == file.modified_at {
+                return Ok(known.content());
+            }
+        }
+
+        let data = fs::read(path)?;
+        Ok(chunker::seal(&data, cipher).into_iter().map(|(chunk, _)| chunk).collect())
+    }
+}
+
+/// Location of an entry of the tree mirrored in a local directory
+pub(super) fn local_path(dir: &Path, path: &str) -> PathBuf {
+    dir.join(path.trim_matches('/'))
+}
+
+impl FS0X {
+    /// Publish a local directory as the new version of the tree
+    ///
+    /// Only the file contents not stored yet are uploaded, files keeping the
+    /// size and modification time they were published with are not even
+    /// read. On a dry run, the changes are only returned.
+    pub fn push(&mut self, dir: &Path, dry_run: bool) -> Result<Diff, LocalError> {
+        // Nothing published yet, the directory is pushed from scratch.
+        self.fs_tree = self.rt.block_on(fetch_fs_tree(&self.asdk, &self.source()))
+            .map_err(|e| LocalError::Fetch(self.channel(), e))?
+            .map(|(fs_tree, _)| fs_tree)
+            .unwrap_or_else(|| FSTree::new("/".to_string()));
+        self.record_stored_contents();
+        let local = read_changed_tree(dir, self.cipher.as_ref(), &self.fs_tree)?;
+
+        let diff = Diff::between(&self.fs_tree, &local);
+        if dry_run || diff.is_empty() {
+            return Ok(diff);
+        }
+
+        let mut ops = diff.operations();
+        self.upload_contents(dir, &mut ops)?;
+        self.apply_operations(&ops);
+
+        self.publish_operations(ops)
+            .and_then(|_| self.wait_for_confirmation())
+            .map_err(LocalError::Publish)?;
+
+        Ok(diff)
+    }
+
+    /// Upload the content of the local files the operations create or
+    /// update, when not stored yet
+    pub(super) fn upload_contents(&mut self, dir: &Path, ops: &mut [Operation]) -> Result<(), LocalError> {
+        for op in ops {
+            let file = match op {
+                Operation::Create { entry: Entry::File(file) } | Operation::Setattr { entry: Entry::File(file) } => file,
+                _ => continue,
+            };
+            if file.content().iter().all(|chunk| self.is_stored(&chunk.hash)) {
+                continue;
+            }
+
+            // The file is read again, it may have changed since the scan.
+            let data = fs::read(local_path(dir, &file.path))?;
+            let chunks = self.store_content(&data).map_err(|e| LocalError::Upload(file.path.clone(), e))?;
+            file.set_content(chunks);
+        }
+
+        Ok(())
+    }
+
+    /// Apply operations to the local tree, skipping the ones that do not fit
+    pub(super) fn apply_operations(&mut self, ops: &[Operation]) {
+        for op in ops {
+            if let Err(e) = op.apply(&mut self.fs_tree) {
+                eprintln!("skipping operation {:?}: {}", op, e);
+            }
+        }
+    }
+
+    /// Write the latest version of the tree to a local directory
+    ///
+    /// Only the changed entries are written, local entries missing from the
+    /// tree are kept unless `delete` is set. On a dry run, the changes are
+    /// only returned.
+    pub fn pull(&mut self, dir: &Path, delete: bool, dry_run: bool) -> Result<Diff, LocalError> {
+        self.fs_tree = self.rt.block_on(fetch_fs_tree(&self.asdk, &self.source()))
+            .map_err(|e| LocalError::Fetch(self.channel(), e))?
+            .map(|(fs_tree, _)| fs_tree)
+            .ok_or_else(|| LocalError::NotFound(self.channel()))?;
+        let local = match dir.exists() {
+            true => read_changed_tree(dir, self.cipher.as_ref(), &self.fs_tree)?,
+            false => FSTree::new("/".to_string()),
+        };
+
+        let mut diff = Diff::between(&local, &self.fs_tree);
+        if !delete {
+            diff.changes.retain(|change| !matches!(change, Change::Removed(_)));
+        }
+        if dry_run || diff.is_empty() {
+            return Ok(diff);
+        }
+        fs::create_dir_all(dir)?;
+
+        // Files are moved first, the directories they leave may be replaced.
+        for change in &diff.changes {
+            if let Change::Moved { before, after } = change {
+                let to = local_path(dir, after.path());
+                if let Some(parent) = to.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::rename(local_path(dir, before.path()), to)?;
+            }
+        }
+
+        // Changes are ordered by path, parents are written before their
+        // children.
+        for change in &diff.changes {
+            match change {
+                Change::Added(after) => self.write_entry(dir, after, false)?,
+                Change::Modified { before, after } => match (&**before, &**after) {
+                    // Only the attributes of the file changed.
+                    (Entry::File(before), Entry::File(after)) if before.content() == after.content() => {},
+                    (before, after) => {
+                        let replaced = before.entry_type() != after.entry_type() || matches!(after, Entry::SymLink(_));
+                        self.write_entry(dir, after, replaced)?;
+                    },
+                },
+                _ => {},
+            }
+        }
+
+        for change in diff.changes.iter().rev() {
+            if let Change::Removed(before) = change {
+                remove_local(&local_path(dir, before.path()))?;
+            }
+        }
+
+        // Attributes are set children first, so that writing an entry does
+        // not change the modification time of its directory afterwards.
+        let mut owner_failures = 0;
+        for change in diff.changes.iter().rev() {
+            let after = match change {
+                Change::Removed(_) => continue,
+                Change::Added(after) => after,
+                Change::Modified { after, .. } | Change::Moved { after, .. } | Change::PermissionChanged { after, .. } => after,
+            };
+
+            if !set_attributes(&local_path(dir, after.path()), after)? {
+                owner_failures += 1;
+            }
+        }
+        if owner_failures > 0 {
+            eprintln!("warning: could not set the owner of {} entries", owner_failures);
+        }
+
+        Ok(diff)
+    }
+
+    /// Create or overwrite the local copy of an entry, removing the entry
+    /// found at its path first when `replace` is set
+    fn write_entry(&mut self, dir: &Path, entry: &Entry, replace: bool) -> Result<(), LocalError> {
+        let path = local_path(dir, entry.path());
+        if replace {
+            remove_local(&path)?;
+        }
+
+        match entry {
+            Entry::Directory(_) => fs::create_dir_all(&path)?,
+            Entry::File(file) => {
+                let data = self.fetch_chunks(&file.content()).map_err(|e| LocalError::Download(file.path.clone(), e))?;
+                // A read-only copy is made writable again for the update.
+                if let Ok(metadata) = fs::symlink_metadata(&path) {
+                    fs::set_permissions(&path, fs::Permissions::from_mode(metadata.permissions().mode() | 0o200))?;
+                }
+                fs::write(&path, data)?;
+            },
+            Entry::SymLink(link) => symlink(&link.link_to, &path)?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Remove a local entry along with its children, if it exists
+fn remove_local(path: &Path) -> io::Result<()> {
+    let result = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Set the permission, owner and modification time of a local entry
+///
+/// Returns whether the owner could be set, only root may give an entry to
+/// another user.
+fn set_attributes(path: &Path, entry: &Entry) -> io::Result<bool> {
+    let (uid, gid) = entry.owner();
+    let owned = lchown(path, Some(uid), Some(gid)).is_ok();
+
+    // Symbolic links have no permission or time of their own to set.
+    if matches!(entry, Entry::SymLink(_)) {
+        return Ok(owned);
+    }
+
+    fs::File::open(path)?.set_modified(entry.modified_at())?;
+    if let Ok(mode) = u32::from_str_radix(entry.permission(), 8) {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o7777))?;
+    }
+
+    Ok(owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_sets_the_attributes_of_local_copies() {
+        let dir = std::env::temp_dir().join(format!("fs0x-pull-{}", std::process::id()));
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        fs::write(dir.join("docs/a.txt"), b"hello").unwrap();
+
+        let mut fs_tree = read_local_tree(&dir, None).unwrap();
+        let mut entry = fs_tree.get_entry("/docs/a.txt/").unwrap().clone();
+        entry.set_permission("100600".to_string());
+        entry.set_modified_at(std::time::UNIX_EPOCH + std::time::Duration::from_secs(60));
+        set_attributes(&local_path(&dir, entry.path()), &entry).unwrap();
+        fs_tree.add_entry(entry);
+
+        let copy = read_local_tree(&dir, None).unwrap();
+        remove_local(&dir.join("docs")).unwrap();
+        remove_local(&dir.join("docs")).unwrap();
+        let left = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(Diff::between(&fs_tree, &copy).is_empty());
+        assert_eq!(left, 0);
+    }
+}
@@ -1,236 +1,1303 @@
+mod cache;
+mod chunker;
+mod handle;
+mod index;
+mod snapshots;
+mod spool;
 mod inode;
-
-use inode::{Inode, InodeAttributes, FileKind};
+mod local;
+mod restore;
+mod status;
+mod sync;
+mod watch;
+mod xattr;
+
+use handle::FileHandle;
+use index::InodeIndex;
+use inode::{Inode, InodeAttributes, FileKind, BLOCK_SIZE};
+use snapshots::{Snapshots, SNAPSHOTS_DIR, SNAPSHOTS_INODE};
+use xattr::{check_xattr_access, decode_xattrs, encode_xattrs, list_xattrs};
+
+pub use cache::ContentCache;
+pub use local::read_local_tree;
+pub use restore::fetch_tree;
+pub use spool::Spool;
+pub use status::Status;
+pub use sync::SyncWorker;
+pub use sync::TreeSource;
+use sync::{fetch_fs_tree, fetch_snapshots, Invalidation, OPERATIONS_TYPE, SNAPSHOT_TYPE};
 
 use fuser::{
-    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry,
-    Request, KernelConfig, ReplyCreate, ReplyWrite,
+    FileAttr, Filesystem, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry,
+    Request, KernelConfig, ReplyCreate, ReplyWrite, ReplyOpen, ReplyEmpty,
+    ReplyStatfs, ReplyXattr, TimeOrNow,
 };
 
 use libc::c_int;
+use std::borrow::Cow;
 use std::ffi::OsStr;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
 use std::sync::atomic::AtomicU64;
-use std::collections::BTreeMap;
-use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::sync::mpsc::{self, Receiver, Sender};
 
-use crate::mirroring::{Directory, Entry, EntryType, FSTree, File};
+use crate::crypto::Cipher;
+use crate::mirroring::{Chunk, Directory, Entry, FSTree, FSTreeError, File, Operation, OperationBatch, SymLink};
 use crate::sdk::common::{DefaultEthereumSigner, MessageSigner};
-use crate::sdk::post::v0::req_objects::{CreatePostRequest, ListPostsRequest};
+use crate::sdk::message::v0::res_objects::MessageStatus;
+use crate::sdk::post::v0::req_objects::CreatePostRequest;
+use crate::sdk::store::v0::req_objects::CreateStoreRequest;
 use crate::sdk::{self, AlephSDK};
-use serde_json;
+use sha2::{Digest, Sha256};
 use tokio::runtime::Runtime;
 
 const TTL: Duration = Duration::from_secs(1);
 
+/// Bytes of Aleph storage granted per ALEPH token held
+const STORAGE_BYTES_PER_ALEPH: f64 = 3.0 * 1024.0 * 1024.0;
+
+/// How long the storage allowance derived from the balance is cached
+const ALLOWANCE_TTL: Duration = Duration::from_secs(300);
+
+/// Longest file name accepted, as on most local filesystems
+const MAX_NAME_LENGTH: u32 = 255;
+
+/// Number of batches of operations posted between two snapshots of the FSTree
+const SNAPSHOT_INTERVAL: usize = 50;
+
+/// How long `fsync` waits for the Aleph node to process the posted messages
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Interval between two checks of the messages waited for by `fsync`
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Name of the Aleph channel a filesystem of an account is mirrored on
+pub fn channel_name(address: &str, id: &str) -> String {
+    format!("fs0x-{}-{}", address, id)
+}
+
+/// Milliseconds elapsed since the UNIX epoch
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
 /// Struct that contains the filesystem inodes and fd handles counter
 pub struct FS0X {
     inodes: BTreeMap<Inode, InodeAttributes>,
+    /// Inodes by name in their parent directory and by path
+    index: InodeIndex,
     file_handles: AtomicU64,
+    open_files: BTreeMap<u64, FileHandle>,
     asdk: AlephSDK,
+    signer: DefaultEthereumSigner,
     fs_tree: FSTree,
+    /// Paths of local entries that were not seen in a fetched FSTree yet
+    pending: BTreeSet<String>,
+    /// Paths of local deletions that were not seen in a fetched FSTree yet
+    deleted: BTreeSet<String>,
+    /// Operations applied to the local FSTree that failed to be posted
+    unpublished: Vec<Operation>,
+    /// Batches of operations posted since the last snapshot of the FSTree
+    batches_since_snapshot: usize,
+    /// Sequence number of the last batch of operations we posted
+    seq: u64,
     id: String,
+    /// Addresses whose posts on the channel are trusted, ours included
+    senders: Vec<String>,
+    /// Encryption of the published tree and file contents, if enabled
+    cipher: Option<Cipher>,
+    /// Local copy of the downloaded file contents, if enabled
+    cache: Option<ContentCache>,
+    /// Local copy of the file contents not uploaded yet, if enabled
+    spool: Option<Spool>,
+    /// Contents found in the spool on mount, waiting for their file to be known
+    spooled: Vec<(String, Vec<u8>)>,
+    /// Past versions of the tree, browsable under `.snapshots`
+    snapshots: Snapshots,
+    /// Hashes of the contents known to be stored, uploaded since the mount
+    /// or listed in an applied tree
+    stored_chunks: HashSet<String>,
+    /// Hashes of the messages posted but not processed by the Aleph node yet
+    unconfirmed: Vec<String>,
+    /// Capacity of the mount in bytes, set by the user
+    quota: Option<u64>,
+    /// Storage allowance of the account in bytes, with the time it was fetched
+    allowance: Option<(SystemTime, u64)>,
+    /// Trees fetched by the background sync worker
+    trees: Option<Receiver<FSTree>>,
+    /// Changes to notify the kernel of through the background sync worker
+    invalidations: Option<Sender<Invalidation>>,
+    /// Runtime every request to Aleph runs on, shared with the sync worker
+    /// so that pooled connections stay driven
+    rt: Runtime,
 }
 
 impl FS0X {
     /// Create a new FS0X filesystem instance
-    pub fn new(asdk: AlephSDK, id: String) -> Self {
+    pub fn new(asdk: AlephSDK, signer: DefaultEthereumSigner, id: String) -> Self {
+        let senders = vec![signer.get_address()];
+
         Self {
             inodes: BTreeMap::new(),
+            index: InodeIndex::new(),
             file_handles: AtomicU64::new(1),
-            asdk: asdk,
+            open_files: BTreeMap::new(),
+            asdk,
+            signer,
             fs_tree: FSTree::new("/".to_string()),
-            id: id,
+            pending: BTreeSet::new(),
+            deleted: BTreeSet::new(),
+            unpublished: Vec::new(),
+            batches_since_snapshot: 0,
+            seq: 0,
+            id,
+            senders,
+            cipher: None,
+            cache: None,
+            spool: None,
+            spooled: Vec::new(),
+            snapshots: Snapshots::new(),
+            stored_chunks: HashSet::new(),
+            unconfirmed: Vec::new(),
+            quota: None,
+            allowance: None,
+            trees: None,
+            invalidations: None,
+            rt: Runtime::new().unwrap(),
         }
     }
 
+    /// Trust the changes posted by other accounts on the channel
+    pub fn with_allowed_senders(mut self, senders: Vec<String>) -> Self {
+        self.senders.extend(senders);
+        self
+    }
+
+    /// Encrypt the published tree and file contents
+    pub fn with_cipher(mut self, cipher: Cipher) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// Serve the file contents from a local cache once downloaded
+    pub fn with_cache(mut self, cache: ContentCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Keep the file contents that failed to upload in a spool directory
+    ///
+    /// Contents left there by a previous mount are uploaded again.
+    pub fn with_spool(mut self, spool: Spool) -> Self {
+        match spool.load() {
+            Ok(spooled) => self.spooled = spooled,
+            Err(e) => eprintln!("failed to read the spool in {}: {}", spool.dir().display(), e),
+        }
+        self.spool = Some(spool);
+        self
+    }
+
+    /// Report a fixed capacity instead of the account storage allowance
+    pub fn with_quota(mut self, quota: u64) -> Self {
+        self.quota = Some(quota);
+        self
+    }
+
+    /// Hand the fetching of the FSTree over to a background worker
+    ///
+    /// The worker fetches the tree every `interval` and has to be spawned
+    /// with the notifier of the session the filesystem is mounted with.
+    pub fn sync_worker(&mut self, interval: Duration) -> SyncWorker {
+        let (trees_sender, trees) = mpsc::channel();
+        let (invalidations, invalidations_receiver) = mpsc::channel();
+
+        self.trees = Some(trees);
+        self.invalidations = Some(invalidations);
+
+        SyncWorker::new(self.asdk.clone(), self.rt.handle().clone(), self.source(), interval, trees_sender, invalidations_receiver)
+    }
+
+    /// Capacity of the mount in bytes
+    ///
+    /// Without a quota, it is the storage allowance of the account ALEPH
+    /// balance, `None` when the balance cannot be fetched.
+    fn capacity(&mut self) -> Option<u64> {
+        if self.quota.is_some() {
+            return self.quota;
+        }
+
+        match self.allowance {
+            Some((fetched_at, allowance)) if fetched_at.elapsed().unwrap_or(ALLOWANCE_TTL) < ALLOWANCE_TTL => {
+                return Some(allowance);
+            },
+            _ => {}
+        }
+
+        let address = self.signer.get_address();
+        match self.rt.block_on(self.asdk.account().v0().balance(&address)) {
+            Ok(balance) => {
+                let allowance = (balance.balance * STORAGE_BYTES_PER_ALEPH) as u64;
+                self.allowance = Some((SystemTime::now(), allowance));
+                Some(allowance)
+            },
+            Err(e) => {
+                eprintln!("failed to fetch the balance of {}: {}", address, e);
+                self.allowance.map(|(_, allowance)| allowance)
+            }
+        }
+    }
+
+    /// Attributes of an inode, the virtual ones of `.snapshots` included
+    fn attributes(&self, inode: Inode) -> Option<Cow<'_, InodeAttributes>> {
+        match self.inodes.get(&inode) {
+            Some(attr) => Some(Cow::Borrowed(attr)),
+            None => self.snapshots.attributes(inode).map(Cow::Owned),
+        }
+    }
+
+    /// Whether a name is `.snapshots` at the root or lives below it
+    fn is_snapshot_name(&self, parent: Inode, name: &OsStr) -> bool {
+        Snapshots::contains(parent) || (parent == fuser::FUSE_ROOT_ID && name == SNAPSHOTS_DIR)
+    }
+
+    /// Fetch the snapshots posted since the last fetch, when it is stale
+    fn refresh_snapshots(&mut self) {
+        if !self.snapshots.is_stale() {
+            return;
+        }
+
+        match self.rt.block_on(fetch_snapshots(&self.asdk, &self.source(), self.snapshots.since())) {
+            Some(snapshots) => self.snapshots.update(snapshots),
+            None => self.snapshots.touch(),
+        }
+    }
+
+    /// Lookup `.snapshots`, or a name below it
+    fn lookup_snapshot(&mut self, parent: Inode, name: &OsStr) -> Result<InodeAttributes, c_int> {
+        if parent == fuser::FUSE_ROOT_ID || parent == SNAPSHOTS_INODE {
+            self.refresh_snapshots();
+        }
+
+        match parent {
+            fuser::FUSE_ROOT_ID => self.snapshots.attributes(SNAPSHOTS_INODE),
+            _ => self.snapshots.lookup(parent, name.to_str().ok_or(libc::ENOENT)?),
+        }.ok_or(libc::ENOENT)
+    }
+
+    /// Content of a directory below `.snapshots`
+    fn snapshot_children(&mut self, inode: Inode) -> Option<Vec<(Inode, fuser::FileType, String)>> {
+        if inode == SNAPSHOTS_INODE {
+            self.refresh_snapshots();
+        }
+
+        self.snapshots.children(inode)
+    }
+
+    /// Open a file of a past version of the tree, loading its content
+    fn open_snapshot_file(&mut self, inode: Inode, flags: i32) -> Result<u64, c_int> {
+        if flags & libc::O_ACCMODE != libc::O_RDONLY || flags & libc::O_TRUNC != 0 {
+            return Err(libc::EROFS);
+        }
+
+        let content = match self.snapshots.entry(inode) {
+            Some(Entry::File(file)) => file.content(),
+            Some(_) => {
+                return Err(libc::EISDIR);
+            },
+            None => {
+                return Err(libc::ENOENT);
+            }
+        };
+        let data = self.fetch_chunks(&content)?;
+
+        let fh = self.file_handles.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let mut handle = FileHandle::new(inode, Some(data), false);
+        handle.content = content;
+        self.open_files.insert(fh, handle);
+
+        Ok(fh)
+    }
+
     /// Lookup a file or directory by name from a parent directory
     pub fn lookup_name(&self, parent: Inode, name: &OsStr) -> Result<InodeAttributes, c_int> {
-        if let Some(attr) = self.inodes.get(&parent) {
-            if let FileKind::Directory(entries) = &attr.kind {
-                for inode in entries {
-                    if let Some(attr) = self.inodes.get(&inode) {
-                        if attr.fname == name.to_str().unwrap() {
-                            return Ok(attr.clone());
-                        }
-                    }
+        self.index.lookup(parent, name.to_str().ok_or(libc::ENOENT)?)
+            .and_then(|inode| self.inodes.get(&inode))
+            .cloned()
+            .ok_or(libc::ENOENT)
+    }
+
+    /// Name of the Aleph channel the filesystem is mirrored on
+    fn channel(&self) -> String {
+        channel_name(&self.signer.get_address(), &self.id)
+    }
+
+    /// Channel the FSTree is published on, as read by peers
+    fn source(&self) -> TreeSource {
+        TreeSource {
+            channel: self.channel(),
+            senders: self.senders.clone(),
+            cipher: self.cipher.clone(),
+        }
+    }
+
+    /// Bring the local inodes up to date with the latest published FSTree
+    ///
+    /// With a background worker, only the trees it already fetched are
+    /// applied, otherwise the tree is fetched inline.
+    fn sync(&mut self) {
+        self.retry_released_handles();
+        if !self.unpublished.is_empty() {
+            let _ = self.publish_operations(vec![]);
+        }
+
+        let fs_tree = match &self.trees {
+            Some(trees) => trees.try_iter().last(),
+            None => match self.rt.block_on(fetch_fs_tree(&self.asdk, &self.source())) {
+                Ok(fetched) => fetched.map(|(fs_tree, _)| fs_tree),
+                Err(e) => {
+                    eprintln!("failed to fetch the tree of {}: {}", self.channel(), e);
+                    None
                 }
+            },
+        };
+
+        if let Some(fs_tree) = fs_tree {
+            self.apply_fs_tree(fs_tree);
+
+            if !self.spooled.is_empty() {
+                self.recover_spooled_contents();
+                self.retry_released_handles();
             }
         }
-
-        return Err(libc::ENOENT);
     }
 
-    fn sync(&mut self) {
-        let signer = DefaultEthereumSigner::new("0xdcf2cbdd171a21c480aa7f53d77f31bb102282b3ff099c78e3118b37348c72f7".to_string()).unwrap();
-        let account_address = signer.get_address();
+    /// Replace the local FSTree with a fetched one and update the inodes
+    fn apply_fs_tree(&mut self, mut fs_tree: FSTree) {
+        self.merge_pending_entries(&mut fs_tree);
+        let previous = std::mem::replace(&mut self.fs_tree, fs_tree);
+        self.record_stored_contents();
+        let mut invalidations: Vec<Invalidation> = self.remove_stale_inodes()
+            .into_iter()
+            .map(|(parent, name)| Invalidation::Entry(parent, name))
+            .collect();
+
+        for entry in self.fs_tree.get_entries_by_path() {
+            let found = self.get_inode_from_path(entry.path()).map(|attr| attr.inode);
+
+            if let Some(inode) = found {
+                let mut dirty = false;
+
+                if let Entry::File(file) = entry {
+                    for handle in self.open_files.values_mut().filter(|handle| handle.inode == inode) {
+                        handle.invalidate(&file.content());
+                        dirty |= handle.dirty;
+                    }
+                }
 
-        let params = ListPostsRequest::default()
-            .with_channels(vec![format!("fs0x-{}-{}", account_address, self.id)]);
+                if previous.get_entry(entry.path()) != Some(entry) {
+                    invalidations.push(Invalidation::Inode(inode));
+                }
 
-        let mut rt = Runtime::new().unwrap();
-        let res = rt.block_on(self.asdk.post().v0().list::<String>(params));
+                if let Some(attr) = self.inodes.get_mut(&inode) {
+                    let (uid, gid) = entry.owner();
 
-        match res {
-            Ok(data) => {
-                println!("FS TREE FETCHED: {:?}\n", data);
-
-                if let Some(post) = data.posts.first() {
-                    let post_content = post.content.clone();
-                    let fs_tree: FSTree = serde_json::from_str(&post_content).unwrap();
-                    self.fs_tree = fs_tree;
-
-                    for entry in self.fs_tree.entries.iter() {
-                        let mut found = false;
-                        for inode in self.inodes.values() {
-                            if self.get_full_path(inode.inode) == entry.path() {
-                                found = true;
-                                break;
-                            }
-                        }
+                    attr.mode = u16::from_str_radix(entry.permission(), 8).unwrap_or(attr.mode);
+                    attr.uid = uid;
+                    attr.gid = gid;
+                    attr.xattrs = decode_xattrs(entry.xattrs());
 
-                        if !found {
-                            let new_inode = self.inodes.last_key_value().unwrap().0 + 1;
-                            let path = std::path::Path::new(entry.path());
+                    // Writes not uploaded yet are more recent than the tree.
+                    if !dirty {
+                        attr.last_modified = entry.modified_at();
+                        if let Entry::File(file) = entry {
+                            attr.size = file.size;
+                        }
+                    }
 
-                            let mut parent_path = match path.parent() {
-                                Some(parent) => parent.to_str().unwrap().to_string(),
-                                None => {
-                                    println!("No parent found");
-                                    return;
-                                }
-                            };
+                    if let (Entry::SymLink(link), FileKind::Symlink(target)) = (entry, &mut attr.kind) {
+                        target.clone_from(&link.link_to);
+                        attr.size = link.link_to.len() as u64;
+                    }
+                }
+            } else {
+                let mode = match u16::from_str_radix(entry.permission(), 8) {
+                    Ok(mode) => mode,
+                    Err(_) => {
+                        eprintln!("skipping {} with invalid permission {:?}", entry.path(), entry.permission());
+                        continue;
+                    }
+                };
 
-                            if !parent_path.ends_with('/') {
-                                parent_path.push('/');
-                            }
+                let new_inode = self.inodes.last_key_value().unwrap().0 + 1;
+                let path = std::path::Path::new(entry.path());
 
-                            println!("TO CHECK :::: PARENT PATH: {:?}", parent_path);
+                let mut parent_path = match path.parent() {
+                    Some(parent) => parent.to_str().unwrap().to_string(),
+                    None => {
+                        eprintln!("no parent directory found for {}", entry.path());
+                        continue;
+                    }
+                };
 
-                            let pinode = match self.get_inode_from_path(&parent_path) {
-                                Some(d) => d,
-                                None => {
-                                    println!("No parent found");
-                                    return;
-                                }
-                            };
-
-                            match self.inodes.get_mut(&pinode.inode) {
-                                Some(attr) => {
-                                    match &mut attr.kind {
-                                        FileKind::Directory(entries) => {
-                                            entries.push(new_inode);
-                                        },
-                                        _ => {
-                                            println!("No parent found");
-                                            return;
-                                        }
-                                    }
-                                },
-                                None => {
-                                    println!("No parent found");
-                                    return;
-                                }
-                            }
+                if !parent_path.ends_with('/') {
+                    parent_path.push('/');
+                }
 
-                            match entry.entry_type() {
-                                EntryType::File => {
-                                    let new_attr = InodeAttributes {
-                                        inode: new_inode,
-                                        pinode: Some(pinode.inode),
-                                        fname: entry.name().to_string(),
-                                        open_file_handles: 0,
-                                        size: 0,
-                                        last_accessed: std::time::SystemTime::now(),
-                                        last_modified: std::time::SystemTime::now(),
-                                        last_metadata_changed: std::time::SystemTime::now(),
-                                        kind: FileKind::File,
-                                        mode: u16::from_str_radix(&entry.permission(), 8).unwrap(),
-                                        hardlinks: 1,
-                                        uid: 0,
-                                        gid: 0,
-                                        xattrs: Default::default(),
-                                    };
-                                    println!("NEW FILE: {:?}", entry.name());
-                                    self.inodes.insert(new_inode, new_attr);
-                                },
-                                EntryType::Directory => {
-                                    let new_attr = InodeAttributes {
-                                        inode: new_inode,
-                                        pinode: Some(pinode.inode),
-                                        fname: entry.name().to_string(),
-                                        open_file_handles: 0,
-                                        size: 0,
-                                        last_accessed: std::time::SystemTime::now(),
-                                        last_modified: std::time::SystemTime::now(),
-                                        last_metadata_changed: std::time::SystemTime::now(),
-                                        kind: FileKind::Directory(vec![]),
-                                        mode: u16::from_str_radix(&entry.permission(), 8).unwrap(),
-                                        hardlinks: 1,
-                                        uid: 0,
-                                        gid: 0,
-                                        xattrs: Default::default(),
-                                    };
-                                    println!("NEW DIR: {:?}", entry.name());
-                                    self.inodes.insert(new_inode, new_attr);
-                                },
-                                _ => {},
+                let pinode = match self.get_inode_from_path(&parent_path) {
+                    Some(d) => d,
+                    None => {
+                        eprintln!("no parent directory found for {}", entry.path());
+                        continue;
+                    }
+                };
+
+                // Hard links of a file already known locally share its inode.
+                let linked = match entry {
+                    Entry::File(File { link_id: Some(link_id), .. }) => self.fs_tree.get_entries().iter()
+                        .filter(|other| matches!(other, Entry::File(file) if file.link_id.as_ref() == Some(link_id)))
+                        .find_map(|other| self.get_inode_from_path(other.path()))
+                        .map(|attr| attr.inode),
+                    _ => None,
+                };
+
+                match self.inodes.get_mut(&pinode.inode) {
+                    Some(attr) => {
+                        match &mut attr.kind {
+                            FileKind::Directory(entries) => {
+                                entries.push(linked.unwrap_or(new_inode));
+                            },
+                            _ => {
+                                eprintln!("no parent directory found for {}", entry.path());
+                                continue;
                             }
                         }
+                    },
+                    None => {
+                        eprintln!("no parent directory found for {}", entry.path());
+                        continue;
                     }
+                }
+
+                invalidations.push(Invalidation::Entry(pinode.inode, entry.name().to_string()));
+                self.index.insert(pinode.inode, entry.name(), entry.path().to_string(), linked.unwrap_or(new_inode));
 
-                    println!("NEW FS TREE: {:?}\n", self.fs_tree);
+                if let Some(attr) = linked.and_then(|inode| self.inodes.get_mut(&inode)) {
+                    attr.add_link(pinode.inode, entry.name().to_string());
+                    continue;
                 }
+
+                match entry {
+                    Entry::File(file) => {
+                        let new_attr = InodeAttributes {
+                            inode: new_inode,
+                            pinode: Some(pinode.inode),
+                            fname: entry.name().to_string(),
+                            open_file_handles: 0,
+                            size: file.size,
+                            last_accessed: std::time::SystemTime::now(),
+                            last_modified: entry.modified_at(),
+                            last_metadata_changed: std::time::SystemTime::now(),
+                            kind: FileKind::File,
+                            mode,
+                            hardlinks: 1,
+                            uid: entry.owner().0,
+                            gid: entry.owner().1,
+                            xattrs: decode_xattrs(entry.xattrs()),
+                            links: vec![],
+                        };
+                        self.inodes.insert(new_inode, new_attr);
+                    },
+                    Entry::Directory(_) => {
+                        let new_attr = InodeAttributes {
+                            inode: new_inode,
+                            pinode: Some(pinode.inode),
+                            fname: entry.name().to_string(),
+                            open_file_handles: 0,
+                            size: 0,
+                            last_accessed: std::time::SystemTime::now(),
+                            last_modified: entry.modified_at(),
+                            last_metadata_changed: std::time::SystemTime::now(),
+                            kind: FileKind::Directory(vec![]),
+                            mode,
+                            hardlinks: 1,
+                            uid: entry.owner().0,
+                            gid: entry.owner().1,
+                            xattrs: decode_xattrs(entry.xattrs()),
+                            links: vec![],
+                        };
+                        self.inodes.insert(new_inode, new_attr);
+                    },
+                    Entry::SymLink(link) => {
+                        let new_attr = InodeAttributes {
+                            inode: new_inode,
+                            pinode: Some(pinode.inode),
+                            fname: entry.name().to_string(),
+                            open_file_handles: 0,
+                            size: link.link_to.len() as u64,
+                            last_accessed: std::time::SystemTime::now(),
+                            last_modified: entry.modified_at(),
+                            last_metadata_changed: std::time::SystemTime::now(),
+                            kind: FileKind::Symlink(link.link_to.clone()),
+                            mode,
+                            hardlinks: 1,
+                            uid: entry.owner().0,
+                            gid: entry.owner().1,
+                            xattrs: decode_xattrs(entry.xattrs()),
+                            links: vec![],
+                        };
+                        self.inodes.insert(new_inode, new_attr);
+                    },
+                }
+            }
+        }
+
+        if let Some(sender) = &self.invalidations {
+            for invalidation in invalidations {
+                let _ = sender.send(invalidation);
+            }
+        }
+    }
+
+    /// Carry the local entries not published yet over a fetched FSTree
+    ///
+    /// A fetched tree may predate our last posts, in which case replacing the
+    /// local tree with it would drop freshly created or written entries.
+    fn merge_pending_entries(&mut self, fs_tree: &mut FSTree) {
+        // Once the tree saw every batch we posted, its entries are our
+        // changes as merged with the concurrent ones of peers.
+        let seen = fs_tree.head(&self.signer.get_address());
+        if self.unpublished.is_empty() && self.seq > 0 && seen >= self.seq {
+            self.pending.clear();
+            self.deleted.clear();
+        }
+        self.seq = self.seq.max(seen);
+
+        for path in self.pending.clone() {
+            let local = match self.fs_tree.get_entry(&path) {
+                Some(entry) => entry.clone(),
+                None => {
+                    self.pending.remove(&path);
+                    continue;
+                }
+            };
+
+            match fs_tree.get_entry_mut(&path) {
+                Some(remote) if serde_json::to_value(&*remote).ok() == serde_json::to_value(&local).ok() => {
+                    self.pending.remove(&path);
+                },
+                Some(remote) => {
+                    *remote = local;
+                },
+                None => {
+                    fs_tree.add_entry(local);
+                }
+            }
+        }
+
+        for path in self.deleted.clone() {
+            match fs_tree.get_entry(&path) {
+                Some(_) => {
+                    // Whatever a peer added under a directory we removed goes
+                    // away with it.
+                    fs_tree.remove_subtree(&path);
+                },
+                None => {
+                    self.deleted.remove(&path);
+                }
+            }
+        }
+    }
+
+    /// Remove the local inodes whose entry is gone from the FSTree
+    ///
+    /// Files with writes not uploaded yet are kept, their next flush
+    /// publishes them again. Returns the parent and name of the removed
+    /// entries.
+    fn remove_stale_inodes(&mut self) -> Vec<(Inode, String)> {
+        let mut stale = Vec::new();
+
+        for attr in self.inodes.values().filter(|attr| attr.inode != fuser::FUSE_ROOT_ID) {
+            if self.open_files.values().any(|handle| handle.inode == attr.inode && handle.dirty) {
+                continue;
+            }
+
+            let names = attr.pinode.map(|p| (p, attr.fname.clone())).into_iter().chain(attr.links.clone());
+            for (parent, name) in names {
+                let path = self.get_name_path(parent, &name);
+                if self.fs_tree.get_entry(&path).is_none() {
+                    stale.push((path, attr.inode, parent, name));
+                }
+            }
+        }
+
+        // Deepest names go first so that the paths of their parents still
+        // resolve when they are unlinked.
+        stale.sort_by_key(|(path, ..)| std::cmp::Reverse(path.len()));
+
+        stale.into_iter()
+            .map(|(_, inode, parent, name)| {
+                self.unlink_name(inode, parent, &name);
+                (parent, name)
+            })
+            .collect()
+    }
+
+    /// Drop one of the names of an inode, removing the inode with its last name
+    fn unlink_name(&mut self, inode: Inode, parent: Inode, name: &str) {
+        if self.inodes.contains_key(&inode) {
+            self.index.remove(parent, name, &self.get_name_path(parent, name));
+        }
+
+        let linked = match self.inodes.get_mut(&inode) {
+            Some(attr) => attr.unlink(parent, name),
+            None => {
+                return;
+            }
+        };
+
+        if !linked {
+            return self.remove_inode(inode);
+        }
+
+        if let Some(attr) = self.inodes.get_mut(&inode) {
+            attr.last_metadata_changed = std::time::SystemTime::now();
+        }
+        if let Some(parent) = self.inodes.get_mut(&parent) {
+            if let FileKind::Directory(entries) = &mut parent.kind {
+                if let Some(position) = entries.iter().position(|child| *child == inode) {
+                    entries.remove(position);
+                }
+            }
+            parent.last_modified = std::time::SystemTime::now();
+        }
+    }
+
+    /// Remove an inode and detach it from its parent directory
+    fn remove_inode(&mut self, inode: Inode) {
+        let pinode = match self.inodes.remove(&inode) {
+            Some(attr) => attr.pinode,
+            None => {
+                return;
+            }
+        };
+
+        if let Some(parent) = pinode.and_then(|p| self.inodes.get_mut(&p)) {
+            if let FileKind::Directory(entries) = &mut parent.kind {
+                entries.retain(|child| *child != inode);
+            }
+            parent.last_modified = std::time::SystemTime::now();
+            parent.last_metadata_changed = std::time::SystemTime::now();
+        }
+    }
+
+    /// Delete a name of a file or an empty directory and publish the deletion
+    fn delete_name(&mut self, inode: Inode, parent: Inode, name: &str) -> Result<(), c_int> {
+        let path = self.get_name_path(parent, name);
+
+        if let Err(FSTreeError::NotEmpty(_)) = self.fs_tree.remove_entry(&path) {
+            return Err(libc::ENOTEMPTY);
+        }
+
+        self.unlink_name(inode, parent, name);
+        self.pending.remove(&path);
+        self.deleted.insert(path.clone());
+
+        // On failure the deletion stays recorded locally and goes out with
+        // the next published operations.
+        let _ = self.publish_operations(vec![Operation::Delete { path }]);
+
+        Ok(())
+    }
+
+    /// Retry uploading the content of handles released while dirty
+    fn retry_released_handles(&mut self) {
+        let released: Vec<u64> = self.open_files.iter()
+            .filter(|(_, handle)| handle.released)
+            .map(|(fh, _)| *fh)
+            .collect();
+
+        for fh in released {
+            if self.flush_file_handle(fh).is_ok() {
+                self.open_files.remove(&fh);
+            }
+        }
+    }
+
+    /// Get the full path of a file or directory as an inode
+    pub fn get_full_path(&self, inode: Inode) -> String {
+        let mut path = String::new();
+        let mut current_inode = inode;
+
+        while let Some(attr) = self.inodes.get(&current_inode) {
+            path = format!("{}/{}", attr.fname, path);
+            match attr.pinode {
+                Some(p) => {
+                    current_inode = p;
+                },
+                None => {
+                    break;
+                }
+            }
+        }
+
+        path
+    }
+
+    /// Get the path of a name in a directory
+    fn get_name_path(&self, parent: Inode, name: &str) -> String {
+        format!("{}{}/", self.get_full_path(parent), name)
+    }
+
+    /// Get the paths of an inode, the ones of its hard links included
+    fn get_all_paths(&self, inode: Inode) -> Vec<String> {
+        let links = match self.inodes.get(&inode) {
+            Some(attr) => attr.links.clone(),
+            None => {
+                return vec![];
+            }
+        };
+
+        std::iter::once(self.get_full_path(inode))
+            .chain(links.iter().map(|(parent, name)| self.get_name_path(*parent, name)))
+            .collect()
+    }
+
+    pub fn get_inode_from_path(&self, path: &str) -> Option<InodeAttributes> {
+        self.index.resolve(path).and_then(|inode| self.inodes.get(&inode)).cloned()
+    }
+
+    /// Get the FSTree file entry mirroring a file inode
+    fn get_tree_file(&mut self, inode: Inode) -> Option<&mut File> {
+        let path = self.get_full_path(inode);
+
+        match self.fs_tree.get_entry_mut(&path) {
+            Some(Entry::File(file)) => Some(file),
+            _ => None,
+        }
+    }
+
+    /// Update the FSTree file entries of a file inode and its hard links
+    fn update_tree_files(&mut self, inode: Inode, mut update: impl FnMut(&mut File)) {
+        for path in self.get_all_paths(inode) {
+            if let Some(Entry::File(file)) = self.fs_tree.get_entry_mut(&path) {
+                update(file);
+            }
+        }
+    }
+
+    /// Fetch the content of a file inode from the Aleph storage
+    ///
+    /// Returns the content along with the chunks it was stored as.
+    fn fetch_content(&mut self, inode: Inode) -> Result<(Vec<Chunk>, Vec<u8>), c_int> {
+        let content = match self.get_tree_file(inode) {
+            Some(file) => file.content(),
+            None => {
+                return Err(libc::ENOENT);
+            }
+        };
+
+        let data = self.fetch_chunks(&content)?;
+
+        Ok((content, data))
+    }
+
+    /// Download the chunks of a content and join them
+    fn fetch_chunks(&mut self, content: &[Chunk]) -> Result<Vec<u8>, c_int> {
+        let mut data = Vec::with_capacity(content.iter().map(|chunk| chunk.size as usize).sum());
+        for chunk in content {
+            let stored = self.download_content(&chunk.hash)?;
+
+            match &self.cipher {
+                Some(cipher) => data.extend(cipher.open(&stored).map_err(|e| {
+                    eprintln!("failed to decrypt content {}: {}", chunk.hash, e);
+                    libc::EIO
+                })?),
+                None => data.extend(stored),
+            }
+        }
+
+        Ok(data)
+    }
+
+    /// Download stored content, through the local cache when there is one
+    ///
+    /// Content not matching its hash is rejected.
+    fn download_content(&mut self, hash: &str) -> Result<Vec<u8>, c_int> {
+        if let Some(data) = self.cache.as_mut().and_then(|cache| cache.get(hash)) {
+            return Ok(data);
+        }
+
+        let data = self.rt.block_on(self.asdk.store().v0().download(hash))
+            .map_err(|_| libc::EIO)?;
+
+        if !cache::matches_hash(hash, &data) {
+            eprintln!("content downloaded for {} does not match its hash", hash);
+            return Err(libc::EIO);
+        }
+        if let Some(cache) = &mut self.cache {
+            cache.insert(hash, &data);
+        }
+
+        Ok(data)
+    }
+
+    /// Get the open file handle of an inode, loading the file content if needed
+    fn load_file_handle(&mut self, fh: u64, inode: Inode) -> Result<&mut FileHandle, c_int> {
+        match self.open_files.get(&fh) {
+            Some(handle) if handle.inode == inode => {},
+            _ => {
+                return Err(libc::EBADF);
+            }
+        }
+
+        if self.open_files[&fh].data.is_none() {
+            let (content, data) = self.fetch_content(inode)?;
+            let handle = self.open_files.get_mut(&fh).ok_or(libc::EBADF)?;
+
+            handle.data = Some(data);
+            handle.content = content;
+        }
+
+        self.open_files.get_mut(&fh).ok_or(libc::EBADF)
+    }
+
+    /// Whether an inode is a directory or lives below it
+    fn is_within(&self, inode: Inode, directory: Inode) -> bool {
+        let mut current = Some(inode);
+
+        while let Some(ino) = current {
+            if ino == directory {
+                return true;
+            }
+            current = self.inodes.get(&ino).and_then(|attr| attr.pinode);
+        }
+
+        false
+    }
+
+    /// Move one of the names of an inode to a new parent directory and name
+    fn move_name(&mut self, inode: Inode, parent: Inode, name: &str, newparent: Inode, newname: &str) {
+        let now = std::time::SystemTime::now();
+
+        if self.inodes.contains_key(&inode) {
+            let (from, to) = (self.get_name_path(parent, name), self.get_name_path(newparent, newname));
+            self.index.rename((parent, name, &from), (newparent, newname, to));
+        }
+
+        match self.inodes.get_mut(&inode) {
+            Some(attr) => {
+                attr.rename((parent, name), (newparent, newname.to_string()));
+                attr.last_metadata_changed = now;
             },
-            Err(e) => {
-                println!("FS TREE FETCH ERROR: {:?}", e);
+            None => {
+                return;
+            }
+        }
+
+        if let Some(parent) = self.inodes.get_mut(&parent) {
+            if let FileKind::Directory(entries) = &mut parent.kind {
+                if let Some(position) = entries.iter().position(|child| *child == inode) {
+                    entries.remove(position);
+                }
             }
+            parent.last_modified = now;
+        }
+
+        if let Some(parent) = self.inodes.get_mut(&newparent) {
+            if let FileKind::Directory(entries) = &mut parent.kind {
+                entries.push(inode);
+            }
+            parent.last_modified = now;
+        }
+    }
+
+    /// Swap a name of an inode with a name of another inode
+    fn exchange_names(&mut self, a: (Inode, Inode, &str), b: (Inode, Inode, &str)) {
+        let ((a, a_parent, a_name), (b, b_parent, b_name)) = (a, b);
+
+        // Hard links of a same file, nothing changes.
+        if a == b {
+            return;
+        }
+
+        let (a_path, b_path) = (self.get_name_path(a_parent, a_name), self.get_name_path(b_parent, b_name));
+        self.index.exchange((a_parent, a_name, &a_path), (b_parent, b_name, &b_path));
+
+        for (inode, from, to) in [(a, (a_parent, a_name), (b_parent, b_name)), (b, (b_parent, b_name), (a_parent, a_name))] {
+            if let Some(attr) = self.inodes.get_mut(&inode) {
+                attr.rename(from, (to.0, to.1.to_string()));
+                attr.last_metadata_changed = std::time::SystemTime::now();
+            }
+        }
+
+        // Within a same directory, the children stay the same.
+        if a_parent == b_parent {
+            return;
+        }
+
+        for (parent, from, to) in [(a_parent, a, b), (b_parent, b, a)] {
+            if let Some(FileKind::Directory(entries)) = self.inodes.get_mut(&parent).map(|attr| &mut attr.kind) {
+                if let Some(child) = entries.iter_mut().find(|child| **child == from) {
+                    *child = to;
+                }
+            }
+        }
+    }
+
+    /// Track the entries moved in the FSTree until a fetched tree has them
+    ///
+    /// Previous paths are recorded as deletions so that a fetched tree
+    /// predating the move does not bring them back.
+    fn record_moves(&mut self, moves: Vec<(String, String)>) {
+        let new_paths: BTreeSet<String> = moves.iter().map(|(_, to)| to.clone()).collect();
+
+        for (from, _) in moves {
+            self.pending.remove(&from);
+            if !new_paths.contains(&from) {
+                self.deleted.insert(from);
+            }
+        }
+
+        for path in new_paths {
+            self.deleted.remove(&path);
+            self.pending.insert(path);
+        }
+    }
+
+    /// Mirror the metadata of an inode into its FSTree entries
+    ///
+    /// Returns the operations publishing the updated entries.
+    fn update_tree_entry(&mut self, inode: Inode) -> Vec<Operation> {
+        let attr = match self.inodes.get(&inode) {
+            Some(attr) => attr.clone(),
+            None => {
+                return vec![];
+            }
+        };
+        let paths = self.get_all_paths(inode);
+
+        for path in &paths {
+            if let Some(entry) = self.fs_tree.get_entry_mut(path) {
+                entry.set_permission(format!("{:o}", attr.mode));
+                entry.set_owner(attr.uid, attr.gid);
+                entry.set_modified_at(attr.last_modified);
+                entry.set_xattrs(encode_xattrs(&attr.xattrs));
+                self.pending.insert(path.clone());
+            }
+        }
+
+        self.setattr_operations(&paths)
+    }
+
+    /// Operations publishing the FSTree entries stored at some paths
+    fn setattr_operations(&self, paths: &[String]) -> Vec<Operation> {
+        paths.iter()
+            .filter_map(|path| self.fs_tree.get_entry(path))
+            .map(|entry| Operation::Setattr { entry: entry.clone() })
+            .collect()
+    }
+
+    /// Resize the content of a file inode
+    ///
+    /// Through an open handle the change is uploaded when the handle is
+    /// flushed, otherwise the resized content is uploaded right away.
+    fn truncate_inode(&mut self, inode: Inode, fh: Option<u64>, size: u64) -> Result<(), c_int> {
+        if let Some(fh) = fh {
+            self.load_file_handle(fh, inode)?.truncate(size as usize);
+            return Ok(());
+        }
+
+        let mut data = match size {
+            0 => vec![],
+            _ => self.fetch_content(inode)?.1,
+        };
+        data.resize(size as usize, 0);
+
+        let content = self.store_content(&data)?;
+
+        for handle in self.open_files.values_mut().filter(|handle| handle.inode == inode) {
+            handle.invalidate(&content);
+        }
+
+        self.update_tree_files(inode, |file| {
+            file.set_content(content.clone());
+        });
+
+        Ok(())
+    }
+
+    /// Upload the content of a dirty file handle and publish its new chunks
+    fn flush_file_handle(&mut self, fh: u64) -> Result<(), c_int> {
+        let (inode, data) = match self.open_files.get(&fh) {
+            Some(handle) if handle.dirty => (handle.inode, handle.data.clone().unwrap_or_default()),
+            Some(_) => {
+                return Ok(());
+            }
+            None => {
+                return Err(libc::EBADF);
+            }
+        };
+
+        // The file was unlinked while open, its content has nowhere to go.
+        if !self.inodes.contains_key(&inode) {
+            return Ok(());
+        }
+
+        let path = self.get_full_path(inode);
+        match self.upload_file_content(fh, inode, data.clone()) {
+            Ok(_) => {
+                if let Some(spool) = &self.spool {
+                    spool.remove(&path);
+                }
+                Ok(())
+            },
+            Err(error_code) => {
+                self.spool_content(&path, &data);
+                Err(error_code)
+            }
+        }
+    }
+
+    /// Upload the content of a file inode and publish its new chunks
+    fn upload_file_content(&mut self, fh: u64, inode: Inode, data: Vec<u8>) -> Result<(), c_int> {
+        let size = data.len() as u64;
+        let content = self.store_content(&data)?;
+
+        if self.get_tree_file(inode).is_none() {
+            let attr = self.inodes.get(&inode).ok_or(libc::ENOENT)?;
+            let file = File::new(attr.fname.clone(), self.get_full_path(inode), size, format!("{:o}", attr.mode), attr.last_modified, attr.gid, attr.uid);
+
+            self.fs_tree.add_entry(Entry::File(file));
+        }
+
+        self.update_tree_files(inode, |file| {
+            file.set_content(content.clone());
+        });
+
+        let ops = self.update_tree_entry(inode);
+        self.publish_operations(ops)?;
+
+        if let Some(handle) = self.open_files.get_mut(&fh) {
+            handle.dirty = false;
+            handle.content = content;
+        }
+
+        Ok(())
+    }
+
+    /// Upload content to the Aleph storage in content-defined chunks
+    ///
+    /// Chunks already stored, for this file or any other, are not uploaded
+    /// again. Returns the chunks of the content, in order.
+    fn store_content(&mut self, data: &[u8]) -> Result<Vec<Chunk>, c_int> {
+        let mut chunks = Vec::new();
+
+        for (mut chunk, sealed) in chunker::seal(data, self.cipher.as_ref()) {
+            if !self.is_stored(&chunk.hash) {
+                chunk.hash = self.store_chunk(sealed)?;
+            }
+            chunks.push(chunk);
+        }
+
+        Ok(chunks)
+    }
+
+    /// Whether a chunk was uploaded already, by us or a peer
+    fn is_stored(&self, hash: &str) -> bool {
+        self.stored_chunks.contains(hash)
+    }
+
+    /// Remember the contents listed in the current tree as stored
+    fn record_stored_contents(&mut self) {
+        for file in self.fs_tree.get_files() {
+            self.stored_chunks.extend(file.hash.iter().cloned());
+            self.stored_chunks.extend(file.chunks.iter().map(|chunk| chunk.hash.clone()));
+        }
+    }
+
+    /// Upload a chunk to the Aleph storage and pin it with a STORE message
+    fn store_chunk(&mut self, data: Vec<u8>) -> Result<String, c_int> {
+        let hash = self.rt.block_on(self.asdk.store().v0().upload(data.clone()))
+            .map_err(|_| libc::EIO)?;
+
+        let params = CreateStoreRequest {
+            signer: &self.signer,
+            channel: self.channel(),
+            item_hash: hash.clone(),
+            item_type: sdk::common::ItemType::Storage,
+        };
+        let message_hash = self.rt.block_on(self.asdk.store().v0().create(&params))
+            .map_err(|_| libc::EIO)?;
+        self.unconfirmed.push(message_hash);
+
+        if let Some(cache) = &mut self.cache {
+            cache.insert(&hash, &data);
+        }
+        self.stored_chunks.insert(hash.clone());
+
+        Ok(hash)
+    }
+
+    /// Keep the content of a file that failed to upload in the spool
+    fn spool_content(&self, path: &str, data: &[u8]) {
+        let spool = match &self.spool {
+            Some(spool) => spool,
+            None => {
+                return;
+            }
+        };
+        let data = match &self.cipher {
+            Some(cipher) => cipher.seal(data),
+            None => data.to_vec(),
+        };
+
+        if let Err(e) = spool.save(path, &data) {
+            eprintln!("failed to spool the content of {}: {}", path, e);
+        }
+    }
+
+    /// Hand the contents spooled by a previous mount over to released handles
+    ///
+    /// They are uploaded along with the handles that failed to upload, once
+    /// the file they belong to is known.
+    fn recover_spooled_contents(&mut self) {
+        for (path, data) in std::mem::take(&mut self.spooled) {
+            let data = match &self.cipher {
+                Some(cipher) => match cipher.open(&data) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        eprintln!("failed to decrypt the spooled content of {}: {}", path, e);
+                        continue;
+                    }
+                },
+                None => data,
+            };
+
+            let inode = match self.get_inode_from_path(&path) {
+                Some(attr) if attr.kind == FileKind::File => attr.inode,
+                _ => {
+                    eprintln!("no file left at {} for its spooled content, keeping it in the spool", path);
+                    continue;
+                }
+            };
+
+            let fh = self.file_handles.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let mut handle = FileHandle::new(inode, Some(data), true);
+            handle.released = true;
+
+            self.open_files.insert(fh, handle);
         }
     }
 
-    /// Get the full path of a file or directory as an inode
-    pub fn get_full_path(&self, inode: Inode) -> String {
-        let mut path = String::new();
-        let mut current_inode = inode;
+    /// Wait for the Aleph node to process the messages posted so far
+    ///
+    /// Fails when a message is rejected or still not processed after
+    /// `CONFIRMATION_TIMEOUT`.
+    fn wait_for_confirmation(&mut self) -> Result<(), c_int> {
+        let started_at = SystemTime::now();
+        let mut rejected = false;
 
         loop {
-            match self.inodes.get(&current_inode) {
-                Some(attr) => {
-                    path = format!("{}/{}", attr.fname, path);
-                    match attr.pinode {
-                        Some(p) => {
-                            current_inode = p;
-                        },
-                        None => {
-                            break;
-                        }
+            let mut pending = Vec::new();
+
+            for hash in std::mem::take(&mut self.unconfirmed) {
+                match self.rt.block_on(self.asdk.message().v0().status(&hash)) {
+                    Ok(MessageStatus::Processed) => {},
+                    Ok(MessageStatus::Pending) | Err(_) => pending.push(hash),
+                    Ok(status) => {
+                        eprintln!("message {} was not accepted: {:?}", hash, status);
+                        rejected = true;
                     }
-                },
-                None => {
-                    break;
                 }
             }
+            self.unconfirmed = pending;
+
+            if rejected {
+                return Err(libc::EIO);
+            }
+            if self.unconfirmed.is_empty() {
+                return Ok(());
+            }
+            if started_at.elapsed().unwrap_or_default() >= CONFIRMATION_TIMEOUT {
+                eprintln!("{} messages still not processed after {:?}", self.unconfirmed.len(), CONFIRMATION_TIMEOUT);
+                return Err(libc::EIO);
+            }
+
+            std::thread::sleep(CONFIRMATION_POLL_INTERVAL);
         }
+    }
 
-        path
+    /// Post operations applied to the local FSTree on the channel
+    ///
+    /// Operations that failed to be posted before go out first. Every
+    /// `SNAPSHOT_INTERVAL` batches, a snapshot of the whole tree is posted so
+    /// that peers only replay the operations made after it.
+    fn publish_operations(&mut self, ops: Vec<Operation>) -> Result<(), c_int> {
+        self.unpublished.extend(ops);
+        if self.unpublished.is_empty() {
+            return Ok(());
+        }
+
+        // Sequence numbers are the posting time, so that they keep increasing
+        // across mounts of a same identity.
+        let author = self.signer.get_address();
+        let seq = self.seq.max(self.fs_tree.head(&author)).max(unix_millis(SystemTime::now()).saturating_sub(1)) + 1;
+        let batch = OperationBatch::new(author, seq, self.fs_tree.heads().clone(), self.unpublished.clone());
+        self.post(OPERATIONS_TYPE, serde_json::to_string(&batch).unwrap())?;
+
+        self.unpublished.clear();
+        self.seq = seq;
+        self.fs_tree.set_head(&batch.author, seq);
+        self.fs_tree.set_updated_at(batch.at);
+        self.batches_since_snapshot += 1;
+
+        // A failed snapshot is retried after the next batch.
+        if self.batches_since_snapshot >= SNAPSHOT_INTERVAL && self.publish_fs_tree().is_ok() {
+            self.batches_since_snapshot = 0;
+        }
+
+        Ok(())
     }
 
-    pub fn get_inode_from_path(&self, path: &str) -> Option<InodeAttributes> {
-        for inode in self.inodes.values() {
-            if self.get_full_path(inode.inode) == path {
-                return Some(inode.clone());
+    /// Post a snapshot of the local FSTree on the channel
+    fn publish_fs_tree(&mut self) -> Result<(), c_int> {
+        self.post(SNAPSHOT_TYPE, serde_json::to_string(&self.fs_tree).unwrap())
+    }
+
+    /// Post a message of a given type on the channel
+    fn post(&mut self, custom_type: &str, content: String) -> Result<(), c_int> {
+        let content = match &self.cipher {
+            Some(cipher) => cipher.seal_text(&content),
+            None => content,
+        };
+        let params = CreatePostRequest {
+            signer: &self.signer,
+            channel: self.channel(),
+            custom_type: custom_type.to_string(),
+            item_type: sdk::common::ItemType::Inline,
+            content,
+        };
+        let res = self.rt.block_on(self.asdk.post().v0().create(&params));
+
+        match res {
+            Ok(message_hash) => {
+                self.unconfirmed.push(message_hash);
+                Ok(())
+            },
+            Err(e) => {
+                eprintln!("failed to post {} on {}: {}", custom_type, self.channel(), e);
+                Err(libc::EIO)
             }
         }
-
-        None
     }
 }
 
 impl Filesystem for FS0X {
     /// Initialize FS0X filesystem
     fn init(&mut self, _req: &Request, _: &mut KernelConfig) -> Result<(), c_int> {
-        if !self.inodes.contains_key(&fuser::FUSE_ROOT_ID) {
-            let root = InodeAttributes {
+        self.inodes.entry(fuser::FUSE_ROOT_ID).or_insert_with(|| {
+            InodeAttributes {
                 inode: fuser::FUSE_ROOT_ID,
                 pinode: None,
                 fname: "".to_string(),
@@ -245,78 +1312,264 @@ impl Filesystem for FS0X {
                 uid: 0,
                 gid: 0,
                 xattrs: Default::default(),
-            };
-
-            self.inodes.insert(fuser::FUSE_ROOT_ID, root);
-        }
+                links: vec![],
+            }
+        });
 
         Ok(())
     }
 
+    /// Report the use of the content cache on unmount
+    fn destroy(&mut self) {
+        if let Some(cache) = &self.cache {
+            eprintln!("content cache: {} bytes, {}", cache.used_bytes(), cache.stats());
+        }
+    }
+
     fn lookup(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
         self.sync();
 
         if name.to_str() == Some(".") {
-            match self.inodes.get(&parent) {
+            match self.attributes(parent) {
                 Some(attr) => {
-                    return reply.entry(&TTL, &FileAttr::from(attr), 0);
+                    reply.entry(&TTL, &FileAttr::from(&*attr), 0);
                 },
                 None => {
-                    return reply.error(libc::ENOENT);
+                    reply.error(libc::ENOENT);
                 }
             }
         } else if name.to_str() == Some("..") {
-            match self.inodes.get(&parent) {
+            match self.attributes(parent) {
                 Some(attr) => {
                     match attr.pinode {
                         Some(p) => {
-                            match self.inodes.get(&p) {
+                            match self.attributes(p) {
                                 Some(pattr) => {
-                                    return reply.entry(&TTL, &FileAttr::from(pattr), 0);
+                                    reply.entry(&TTL, &FileAttr::from(&*pattr), 0);
                                 },
                                 None => {
-                                    return reply.error(libc::ENOENT);
+                                    reply.error(libc::ENOENT);
                                 }
                             }
                         },
                         None => {
-                            return reply.error(libc::ENOENT);
+                            reply.error(libc::ENOENT);
                         }
                     }
                 },
                 None => {
-                    return reply.error(libc::ENOENT);
+                    reply.error(libc::ENOENT);
+                }
+            }
+        }
+        else if self.is_snapshot_name(parent, name) {
+            match self.lookup_snapshot(parent, name) {
+                Ok(attr) => {
+                    reply.entry(&TTL, &FileAttr::from(&attr), 0);
+                },
+                Err(error_code) => {
+                    reply.error(error_code);
                 }
             }
         }
         else {
             match self.lookup_name(parent, name) {
                 Ok(attr) => {
-                    return reply.entry(&TTL, &FileAttr::from(&attr), 0);
+                    reply.entry(&TTL, &FileAttr::from(&attr), 0);
                 },
                 Err(error_code) => {
-                    return reply.error(error_code);
+                    reply.error(error_code);
                 }
             }
         }
     }
 
     fn getattr(&mut self, _req: &Request, ino: u64, reply: ReplyAttr) {
-        match self.inodes.get(&ino) {
+        match self.attributes(ino) {
             Some(attr) => {
-                return reply.attr(&TTL, &FileAttr::from(attr));
+                reply.attr(&TTL, &FileAttr::from(&*attr));
+            },
+            None => {
+                reply.error(libc::ENOENT);
+            }
+        }
+    }
+
+    fn setattr(
+        &mut self,
+        req: &Request,
+        ino: u64,
+        mode: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        size: Option<u64>,
+        atime: Option<TimeOrNow>,
+        mtime: Option<TimeOrNow>,
+        _ctime: Option<SystemTime>,
+        fh: Option<u64>,
+        _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>,
+        _bkuptime: Option<SystemTime>,
+        _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        if Snapshots::contains(ino) {
+            return reply.error(libc::EROFS);
+        }
+
+        let mut attr = match self.inodes.get(&ino) {
+            Some(attr) => attr.clone(),
+            None => {
+                return reply.error(libc::ENOENT);
+            }
+        };
+        let is_owner = req.uid() == 0 || req.uid() == attr.uid;
+        let now = SystemTime::now();
+
+        if let Some(mode) = mode {
+            if !is_owner {
+                return reply.error(libc::EPERM);
+            }
+
+            attr.mode = (mode & 0o7777) as u16;
+            // Only members of the file group may set its setgid bit.
+            if req.uid() != 0 && req.gid() != attr.gid {
+                attr.mode &= !(libc::S_ISGID as u16);
+            }
+            attr.last_metadata_changed = now;
+        }
+
+        if uid.is_some() || gid.is_some() {
+            let uid = uid.unwrap_or(attr.uid);
+            let gid = gid.unwrap_or(attr.gid);
+
+            // Only root gives a file away, owners may only move it to their
+            // own group.
+            if req.uid() != 0 && (uid != attr.uid || req.uid() != attr.uid || (gid != attr.gid && gid != req.gid())) {
+                return reply.error(libc::EPERM);
+            }
+
+            attr.uid = uid;
+            attr.gid = gid;
+            if attr.kind == FileKind::File {
+                attr.clear_suid_sgid();
+            }
+            attr.last_metadata_changed = now;
+        }
+
+        if let Some(size) = size {
+            match attr.kind {
+                FileKind::File => {},
+                FileKind::Directory(_) => {
+                    return reply.error(libc::EISDIR);
+                },
+                FileKind::Symlink(_) => {
+                    return reply.error(libc::EINVAL);
+                }
+            }
+            if fh.is_none() && !attr.check_access(req.uid(), req.gid(), libc::W_OK) {
+                return reply.error(libc::EACCES);
+            }
+            if let Err(error_code) = self.truncate_inode(ino, fh, size) {
+                return reply.error(error_code);
+            }
+
+            attr.size = size;
+            if req.uid() != 0 {
+                attr.clear_suid_sgid();
+            }
+            attr.last_modified = now;
+            attr.last_metadata_changed = now;
+        }
+
+        let can_write = attr.check_access(req.uid(), req.gid(), libc::W_OK);
+
+        for (time, value) in [(&mut attr.last_accessed, atime), (&mut attr.last_modified, mtime)] {
+            match value {
+                Some(TimeOrNow::SpecificTime(t)) if is_owner => {
+                    *time = t;
+                },
+                Some(TimeOrNow::Now) if is_owner || can_write => {
+                    *time = now;
+                },
+                Some(_) => {
+                    return reply.error(libc::EPERM);
+                },
+                None => {}
+            }
+        }
+        if atime.is_some() || mtime.is_some() {
+            attr.last_metadata_changed = now;
+        }
+
+        self.inodes.insert(ino, attr.clone());
+        let ops = self.update_tree_entry(ino);
+
+        // On failure the change stays pending in the local tree and goes out
+        // with the next published operations.
+        let _ = self.publish_operations(ops);
+
+        reply.attr(&TTL, &FileAttr::from(&attr));
+    }
+
+    fn statfs(&mut self, _req: &Request, _ino: u64, reply: ReplyStatfs) {
+        let used = self.fs_tree.used_bytes().div_ceil(BLOCK_SIZE);
+        // Without a known capacity, the mount is reported full rather than
+        // pretending to have room left.
+        let blocks = self.capacity().map_or(used, |capacity| capacity / BLOCK_SIZE).max(used);
+        let files = self.inodes.len() as u64;
+
+        reply.statfs(
+            blocks,
+            blocks - used,
+            blocks - used,
+            files,
+            u64::MAX - files,
+            BLOCK_SIZE as u32,
+            MAX_NAME_LENGTH,
+            BLOCK_SIZE as u32,
+        );
+    }
+
+    fn open(&mut self, _req: &Request, ino: u64, flags: i32, reply: ReplyOpen) {
+        if Snapshots::contains(ino) {
+            return match self.open_snapshot_file(ino, flags) {
+                Ok(fh) => reply.opened(fh, 0),
+                Err(error_code) => reply.error(error_code),
+            };
+        }
+
+        match self.inodes.get(&ino) {
+            Some(attr) if attr.kind == FileKind::File => {},
+            Some(_) => {
+                return reply.error(libc::EISDIR);
             },
             None => {
                 return reply.error(libc::ENOENT);
             }
         }
+
+        let fh = self.file_handles.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+
+        let handle = match flags & libc::O_TRUNC != 0 {
+            true => {
+                if let Some(attr) = self.inodes.get_mut(&ino) {
+                    attr.size = 0;
+                }
+                FileHandle::new(ino, Some(vec![]), true)
+            },
+            false => FileHandle::new(ino, None, false),
+        };
+        self.open_files.insert(fh, handle);
+
+        reply.opened(fh, 0);
     }
 
     fn write(
         &mut self,
         _req: &Request,
         inode: u64,
-        _fh: u64,
+        fh: u64,
         offset: i64,
         data: &[u8],
         _write_flags: u32,
@@ -324,58 +1577,102 @@ impl Filesystem for FS0X {
         _lock_owner: Option<u64>,
         reply: ReplyWrite,
     ) {
-        println!("write() called with {:?} size={:?}", inode, data.len());
-        assert!(offset >= 0);
-
-        let _path = self.get_full_path(inode);
-
-        /*if let Ok(mut file) = OpenOptions::new().write(true).open(path) {
-            file.seek(SeekFrom::Start(offset as u64)).unwrap();
-            file.write_all(data).unwrap();
+        if offset < 0 {
+            return reply.error(libc::EINVAL);
+        }
 
-            let mut attrs = self.get_inode(inode).unwrap();
-            attrs.last_metadata_changed = time_now();
-            attrs.last_modified = time_now();
-            if data.len() + offset as usize > attrs.size as usize {
-                attrs.size = (data.len() + offset as usize) as u64;
+        let size = match self.load_file_handle(fh, inode) {
+            Ok(handle) => handle.write(offset as usize, data),
+            Err(error_code) => {
+                return reply.error(error_code);
             }
-            clear_suid_sgid(&mut attrs);
-            self.write_inode(&attrs);
+        };
 
-            reply.written(data.len() as u32);
-        } else {
-            reply.error(libc::EBADF);
-        }*/
+        if let Some(attr) = self.inodes.get_mut(&inode) {
+            attr.size = size;
+            attr.last_modified = std::time::SystemTime::now();
+            attr.last_metadata_changed = std::time::SystemTime::now();
+        }
+
+        reply.written(data.len() as u32);
     }
 
     fn read(
         &mut self,
         _req: &Request,
         ino: u64,
-        _fh: u64,
+        fh: u64,
         offset: i64,
-        _size: u32,
+        size: u32,
         _flags: i32,
         _lock: Option<u64>,
         reply: ReplyData,
     ) {
-        match self.inodes.get(&ino) {
-            Some(attr) => {
-                match &attr.kind {
-                    FileKind::File => {
-                        // TODO: here API call to read file from the cloud
-                        // the file path requested is available in `self.get_full_path(ino)`
+        match self.attributes(ino) {
+            Some(attr) if attr.kind == FileKind::File => {},
+            _ => {
+                return reply.error(libc::ENOENT);
+            }
+        }
 
-                        // tmp dummy return implementation
-                        reply.data(&"Hello".as_bytes()[offset as usize..]);
-                    },
-                    _ => {
-                        reply.error(libc::ENOENT);
-                    }
-                }
+        match self.load_file_handle(fh, ino) {
+            Ok(handle) => {
+                reply.data(handle.read(offset as usize, size as usize));
             },
-            None => {
-                reply.error(libc::ENOENT);
+            Err(error_code) => {
+                reply.error(error_code);
+            }
+        }
+    }
+
+    fn flush(&mut self, _req: &Request, _ino: u64, fh: u64, _lock_owner: u64, reply: ReplyEmpty) {
+        match self.flush_file_handle(fh) {
+            Ok(_) => reply.ok(),
+            Err(error_code) => reply.error(error_code),
+        }
+    }
+
+    /// Upload the file content and wait until the Aleph node processed it
+    /// along with every change posted before
+    fn fsync(&mut self, _req: &Request, _ino: u64, fh: u64, _datasync: bool, reply: ReplyEmpty) {
+        match self.flush_file_handle(fh).and_then(|_| self.wait_for_confirmation()) {
+            Ok(_) => reply.ok(),
+            Err(error_code) => reply.error(error_code),
+        }
+    }
+
+    /// Wait until the Aleph node processed the changes posted so far
+    fn fsyncdir(&mut self, _req: &Request, _ino: u64, _fh: u64, _datasync: bool, reply: ReplyEmpty) {
+        match self.publish_operations(vec![]).and_then(|_| self.wait_for_confirmation()) {
+            Ok(_) => reply.ok(),
+            Err(error_code) => reply.error(error_code),
+        }
+    }
+
+    fn release(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        fh: u64,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        _flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        match self.flush_file_handle(fh) {
+            Ok(_) => {
+                self.open_files.remove(&fh);
+                reply.ok();
+            },
+            Err(error_code) => {
+                // The kernel ignores release errors, keep the content around
+                // so the upload is retried on the next sync.
+                eprintln!("failed to upload {} (error {}), retrying on next sync", self.get_full_path(ino), error_code);
+
+                if let Some(handle) = self.open_files.get_mut(&fh) {
+                    handle.released = true;
+                }
+                reply.error(error_code);
             }
         }
     }
@@ -390,20 +1687,54 @@ impl Filesystem for FS0X {
     ) {
         self.sync();
 
+        if Snapshots::contains(ino) {
+            return match self.snapshot_children(ino) {
+                Some(children) => {
+                    for (i, (inode, kind, name)) in children.iter().enumerate().skip(offset as usize) {
+                        if reply.add(*inode, (i + 1) as i64, *kind, name) {
+                            break;
+                        }
+                    }
+                    reply.ok()
+                },
+                None => reply.error(libc::ENOENT),
+            };
+        }
+
         if let Some(attr) = self.inodes.get(&ino) {
             if let FileKind::Directory(entries) = &attr.kind {
-                for (i, inode) in entries.iter().enumerate().skip(offset as usize) {
-                    if let Some(attr) = self.inodes.get(&inode) {
-                        if reply.add(*inode, (i + 1) as i64, attr.kind.clone().into(), &attr.fname) {
+                // An inode is listed once per hard link it has in the directory.
+                let mut seen: BTreeMap<Inode, usize> = BTreeMap::new();
+                let mut full = false;
+
+                for (i, inode) in entries.iter().enumerate() {
+                    let nth = seen.entry(*inode).or_default();
+                    *nth += 1;
+
+                    if i < offset as usize {
+                        continue;
+                    }
+                    if let Some(attr) = self.inodes.get(inode) {
+                        let name = match attr.names_in(ino).get(*nth - 1) {
+                            Some(name) => name.to_string(),
+                            None => continue,
+                        };
+                        if reply.add(*inode, (i + 1) as i64, attr.kind.clone().into(), &name) {
+                            full = true;
                             break;
                         }
                     }
                 }
+
+                // The past versions of the tree are listed last at the root.
+                if !full && ino == fuser::FUSE_ROOT_ID && offset as usize <= entries.len() {
+                    let _ = reply.add(SNAPSHOTS_INODE, (entries.len() + 1) as i64, fuser::FileType::Directory, SNAPSHOTS_DIR);
+                }
                 return reply.ok();
             }
         }
 
-        return reply.error(libc::ENOENT);
+        reply.error(libc::ENOENT);
     }
 
     fn create(
@@ -411,15 +1742,116 @@ impl Filesystem for FS0X {
         req: &Request,
         parent: u64,
         name: &OsStr,
-        mut mode: u32,
+        mode: u32,
         _umask: u32,
-        flags: i32,
+        _flags: i32,
         reply: ReplyCreate,
     ) {
+        if self.is_snapshot_name(parent, name) {
+            return reply.error(libc::EROFS);
+        }
+
+        if self.lookup_name(parent, name).is_ok() {
+            return reply.error(libc::EEXIST);
+        }
+    
+        let new_inode = self.inodes.last_key_value().unwrap().0 + 1;
+    
+        let new_attr = InodeAttributes {
+            inode: new_inode,
+            pinode: Some(parent),
+            fname: name.to_str().unwrap().to_string(),
+            open_file_handles: 0,
+            size: 0,
+            last_accessed: std::time::SystemTime::now(),
+            last_modified: std::time::SystemTime::now(),
+            last_metadata_changed: std::time::SystemTime::now(),
+            kind: FileKind::File,
+            mode: mode as u16,
+            hardlinks: 1,
+            uid: req.uid(),
+            gid: req.gid(),
+            xattrs: Default::default(),
+            links: vec![],
+        };
+    
+        match self.inodes.get_mut(&parent) {
+            Some(attr) => {
+                match &mut attr.kind {
+                    FileKind::Directory(entries) => {
+                        entries.push(new_inode);
+                    },
+                    _ => {
+                        return reply.error(libc::ENOENT);
+                    }
+                }
+            },
+            None => {
+                return reply.error(libc::ENOENT);
+            }
+        }
+    
+        self.inodes.insert(new_inode, new_attr.clone());
+        self.index.insert(parent, &new_attr.fname, self.get_full_path(new_inode), new_inode);
+        let fd = self.file_handles.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        self.open_files.insert(fd, FileHandle::new(new_inode, Some(vec![]), false));
+
+        let f_full_path = self.get_full_path(new_inode);
+
+        let new_file = File::new(new_attr.fname.clone(), f_full_path, 0, format!("{:o}", new_attr.mode), new_attr.last_modified, new_attr.gid, new_attr.uid);
+        self.fs_tree.add_entry(Entry::File(new_file.clone()));
+        self.deleted.remove(&self.get_full_path(new_inode));
+        self.pending.insert(self.get_full_path(new_inode));
+
+        // On failure the entry stays pending in the local tree and goes out
+        // with the next published operations.
+        let _ = self.publish_operations(vec![Operation::Create { entry: Entry::File(new_file) }]);
+
+        reply.created(
+            &TTL,
+            &(&new_attr).into(),
+            0,
+            fd,
+            0,
+        );
+    }
+
+    fn mkdir(
+        &mut self,
+        req: &Request,
+        parent: u64,
+        name: &OsStr,
+        mut mode: u32,
+        _umask: u32,
+        reply: ReplyEntry,
+    ) {
+        if self.is_snapshot_name(parent, name) {
+            return reply.error(libc::EROFS);
+        }
+
         if self.lookup_name(parent, name).is_ok() {
             return reply.error(libc::EEXIST);
         }
-    
+
+        let mut parent_attrs = match self.inodes.get(&parent) {
+            Some(attrs) => attrs.clone(),
+            None => {
+                return reply.error(libc::EEXIST);
+            }
+        };
+
+        parent_attrs.last_modified = std::time::SystemTime::now();
+        parent_attrs.last_metadata_changed = std::time::SystemTime::now();
+
+        self.inodes.insert(parent, parent_attrs.clone());
+
+        if req.uid() != 0 {
+            mode &= !(libc::S_ISUID | libc::S_ISGID);
+        }
+        if parent_attrs.mode & libc::S_ISGID as u16 != 0 {
+            mode |= libc::S_ISGID;
+        }
+
         let new_inode = self.inodes.last_key_value().unwrap().0 + 1;
     
         let new_attr = InodeAttributes {
@@ -431,14 +1863,15 @@ impl Filesystem for FS0X {
             last_accessed: std::time::SystemTime::now(),
             last_modified: std::time::SystemTime::now(),
             last_metadata_changed: std::time::SystemTime::now(),
-            kind: FileKind::File,
+            kind: FileKind::Directory(vec![]),
             mode: mode as u16,
             hardlinks: 1,
             uid: req.uid(),
             gid: req.gid(),
             xattrs: Default::default(),
+            links: vec![],
         };
-    
+
         match self.inodes.get_mut(&parent) {
             Some(attr) => {
                 match &mut attr.kind {
@@ -456,100 +1889,288 @@ impl Filesystem for FS0X {
         }
     
         self.inodes.insert(new_inode, new_attr.clone());
-        let fd = self.file_handles.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        self.index.insert(parent, &new_attr.fname, self.get_full_path(new_inode), new_inode);
 
         let f_full_path = self.get_full_path(new_inode);
-        println!("TOUCH FILE: {}", f_full_path);
+        let new_dir = Directory::new(new_attr.fname.clone(), f_full_path, format!("{:o}", new_attr.mode), new_attr.last_modified, new_attr.gid, new_attr.uid);
+        self.fs_tree.add_entry(Entry::Directory(new_dir.clone()));
+        self.deleted.remove(&self.get_full_path(new_inode));
+        self.pending.insert(self.get_full_path(new_inode));
 
-        // TODO: here API call to create file in the cloud
-        // the new file path requested is available in `self.get_full_path(new_inode)`
-        let new_file = File::new(new_attr.fname.clone(), f_full_path, 0, format!("{:o}", new_attr.mode), new_attr.last_modified, new_attr.gid, new_attr.uid);
-        self.fs_tree.add_entry(Entry::File(new_file));
+        // On failure the entry stays pending in the local tree and goes out
+        // with the next published operations.
+        let _ = self.publish_operations(vec![Operation::Mkdir { entry: Entry::Directory(new_dir) }]);
 
-        let fs_tree_json = serde_json::to_string(&self.fs_tree).unwrap();
-        println!("FS_TREE: {}", fs_tree_json);
+        reply.entry(&TTL, &(&new_attr).into(), 0);
+    }
 
-        let signer = DefaultEthereumSigner::new("0xdcf2cbdd171a21c480aa7f53d77f31bb102282b3ff099c78e3118b37348c72f7".to_string()).unwrap();
-        let account_address = signer.get_address();
+    fn unlink(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        if self.is_snapshot_name(parent, name) {
+            return reply.error(libc::EROFS);
+        }
 
-        let params = CreatePostRequest {
-            signer: signer,
-            channel: format!("fs0x-{}-{}", account_address, self.id),
-            custom_type: "fs_tree".to_string(),
-            item_type: sdk::common::ItemType::Inline,
-            content: fs_tree_json.clone(),
+        let attr = match self.lookup_name(parent, name) {
+            Ok(attr) => attr,
+            Err(error_code) => {
+                return reply.error(error_code);
+            }
         };
-        let mut rt = Runtime::new().unwrap();
-        let res = rt.block_on(self.asdk.post().v0().create(&params));
 
-        match res {
-            Ok(_) => {
-                println!("FS_TREE POSTED");
-            },
-            Err(e) => {
-                println!("FS_TREE POST ERROR: {:?}", e);
-            }
-            
+        if let FileKind::Directory(_) = attr.kind {
+            return reply.error(libc::EISDIR);
         }
 
-        reply.created(
-            &TTL,
-            &(&new_attr).into(),
-            0,
-            fd,
-            0,
-        );
+        match self.delete_name(attr.inode, parent, name.to_str().unwrap()) {
+            Ok(_) => reply.ok(),
+            Err(error_code) => reply.error(error_code),
+        }
     }
 
-    fn mkdir(
+    fn rename(
         &mut self,
-        req: &Request,
+        _req: &Request,
         parent: u64,
         name: &OsStr,
-        mut mode: u32,
-        _umask: u32,
+        newparent: u64,
+        newname: &OsStr,
+        flags: u32,
+        reply: ReplyEmpty,
+    ) {
+        if self.is_snapshot_name(parent, name) || self.is_snapshot_name(newparent, newname) {
+            return reply.error(libc::EROFS);
+        }
+
+        let attr = match self.lookup_name(parent, name) {
+            Ok(attr) => attr,
+            Err(error_code) => {
+                return reply.error(error_code);
+            }
+        };
+
+        match self.inodes.get(&newparent) {
+            Some(pattr) if matches!(pattr.kind, FileKind::Directory(_)) => {},
+            Some(_) => {
+                return reply.error(libc::ENOTDIR);
+            },
+            None => {
+                return reply.error(libc::ENOENT);
+            }
+        }
+
+        let target = self.lookup_name(newparent, newname).ok();
+        let is_dir = matches!(attr.kind, FileKind::Directory(_));
+
+        // A directory cannot be moved below itself.
+        if is_dir && self.is_within(newparent, attr.inode) {
+            return reply.error(libc::EINVAL);
+        }
+
+        let (name, newname) = (name.to_str().unwrap(), newname.to_str().unwrap());
+        let from = self.get_name_path(parent, name);
+        let to = self.get_name_path(newparent, newname);
+
+        let moves = if flags & libc::RENAME_EXCHANGE != 0 {
+            let target = match target {
+                Some(target) => target,
+                None => {
+                    return reply.error(libc::ENOENT);
+                }
+            };
+            if matches!(target.kind, FileKind::Directory(_)) && self.is_within(parent, target.inode) {
+                return reply.error(libc::EINVAL);
+            }
+
+            let moves = match self.fs_tree.exchange_entries(&from, &to) {
+                Ok(moves) => moves,
+                Err(_) => {
+                    return reply.error(libc::ENOENT);
+                }
+            };
+            self.exchange_names((attr.inode, parent, name), (target.inode, newparent, newname));
+
+            moves
+        } else {
+            if let Some(target) = target {
+                if flags & libc::RENAME_NOREPLACE != 0 {
+                    return reply.error(libc::EEXIST);
+                }
+                if target.inode == attr.inode {
+                    return reply.ok();
+                }
+
+                match (&target.kind, is_dir) {
+                    (FileKind::Directory(entries), true) if !entries.is_empty() => {
+                        return reply.error(libc::ENOTEMPTY);
+                    },
+                    (FileKind::Directory(_), false) => {
+                        return reply.error(libc::EISDIR);
+                    },
+                    (FileKind::File | FileKind::Symlink(_), true) => {
+                        return reply.error(libc::ENOTDIR);
+                    },
+                    _ => {},
+                }
+
+                let _ = self.fs_tree.remove_entry(&to);
+                self.unlink_name(target.inode, newparent, newname);
+            }
+
+            let moves = match self.fs_tree.move_entry(&from, &to) {
+                Ok(moves) => moves,
+                Err(FSTreeError::AlreadyExists(_)) => {
+                    return reply.error(libc::EEXIST);
+                },
+                Err(_) => {
+                    return reply.error(libc::ENOENT);
+                }
+            };
+            self.move_name(attr.inode, parent, name, newparent, newname);
+
+            moves
+        };
+
+        self.record_moves(moves);
+
+        // On failure the moves stay recorded locally and go out with the next
+        // published operations.
+        let _ = self.publish_operations(vec![Operation::Rename {
+            from,
+            to,
+            exchange: flags & libc::RENAME_EXCHANGE != 0,
+        }]);
+
+        reply.ok();
+    }
+
+    fn link(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        newparent: u64,
+        newname: &OsStr,
         reply: ReplyEntry,
     ) {
-        if self.lookup_name(parent, name).is_ok() {
+        if Snapshots::contains(ino) || self.is_snapshot_name(newparent, newname) {
+            return reply.error(libc::EROFS);
+        }
+
+        match self.inodes.get(&ino).map(|attr| &attr.kind) {
+            Some(FileKind::File) => {},
+            Some(_) => {
+                return reply.error(libc::EPERM);
+            },
+            None => {
+                return reply.error(libc::ENOENT);
+            }
+        }
+        match self.inodes.get(&newparent).map(|attr| &attr.kind) {
+            Some(FileKind::Directory(_)) => {},
+            Some(_) => {
+                return reply.error(libc::ENOTDIR);
+            },
+            None => {
+                return reply.error(libc::ENOENT);
+            }
+        }
+        if self.lookup_name(newparent, newname).is_ok() {
             return reply.error(libc::EEXIST);
         }
 
-        let mut parent_attrs = match self.inodes.get(&parent) {
-            Some(attrs) => attrs.clone(),
+        let newname = newname.to_str().unwrap();
+        let path = self.get_name_path(newparent, newname);
+
+        // All the paths of the file are tied together by a shared link id.
+        let mut file = match self.get_tree_file(ino) {
+            Some(file) => file.clone(),
             None => {
-                return reply.error(libc::EEXIST);
+                return reply.error(libc::ENOENT);
             }
         };
+        let link_id = file.link_id.clone().unwrap_or_else(|| {
+            let seed = format!("{}{:?}", file.path, SystemTime::now());
+            hex::encode(Sha256::digest(seed.as_bytes()))
+        });
+        self.update_tree_files(ino, |file| {
+            file.link_id = Some(link_id.clone());
+        });
+        let mut ops = self.setattr_operations(&self.get_all_paths(ino));
+
+        file.name = newname.to_string();
+        file.path.clone_from(&path);
+        file.link_id = Some(link_id);
+        self.fs_tree.add_entry(Entry::File(file.clone()));
+        ops.push(Operation::Create { entry: Entry::File(file) });
+
+        let now = SystemTime::now();
+        if let Some(parent) = self.inodes.get_mut(&newparent) {
+            if let FileKind::Directory(entries) = &mut parent.kind {
+                entries.push(ino);
+            }
+            parent.last_modified = now;
+        }
 
-        parent_attrs.last_modified = std::time::SystemTime::now();
-        parent_attrs.last_metadata_changed = std::time::SystemTime::now();
+        let attr = match self.inodes.get_mut(&ino) {
+            Some(attr) => {
+                attr.add_link(newparent, newname.to_string());
+                attr.last_metadata_changed = now;
+                self.index.insert(newparent, newname, path.clone(), ino);
+                attr.clone()
+            },
+            None => {
+                return reply.error(libc::ENOENT);
+            }
+        };
 
-        self.inodes.insert(parent, parent_attrs.clone());
+        self.deleted.remove(&path);
+        self.pending.insert(path);
 
-        if req.uid() != 0 {
-            mode &= !(libc::S_ISUID | libc::S_ISGID) as u32;
+        // On failure the entry stays pending in the local tree and goes out
+        // with the next published operations.
+        let _ = self.publish_operations(ops);
+
+        reply.entry(&TTL, &FileAttr::from(&attr), 0);
+    }
+
+    fn symlink(
+        &mut self,
+        req: &Request,
+        parent: u64,
+        link_name: &OsStr,
+        target: &Path,
+        reply: ReplyEntry,
+    ) {
+        if self.is_snapshot_name(parent, link_name) {
+            return reply.error(libc::EROFS);
         }
-        if parent_attrs.mode & libc::S_ISGID as u16 != 0 {
-            mode |= libc::S_ISGID as u32;
+
+        if self.lookup_name(parent, link_name).is_ok() {
+            return reply.error(libc::EEXIST);
         }
 
+        let target = match target.to_str() {
+            Some(target) => target.to_string(),
+            None => {
+                return reply.error(libc::EINVAL);
+            }
+        };
         let new_inode = self.inodes.last_key_value().unwrap().0 + 1;
-    
+
         let new_attr = InodeAttributes {
             inode: new_inode,
             pinode: Some(parent),
-            fname: name.to_str().unwrap().to_string(),
+            fname: link_name.to_str().unwrap().to_string(),
             open_file_handles: 0,
-            size: 0,
+            size: target.len() as u64,
             last_accessed: std::time::SystemTime::now(),
             last_modified: std::time::SystemTime::now(),
             last_metadata_changed: std::time::SystemTime::now(),
-            kind: FileKind::Directory(vec![]),
-            mode: mode as u16,
+            kind: FileKind::Symlink(target.clone()),
+            mode: 0o777,
             hardlinks: 1,
             uid: req.uid(),
             gid: req.gid(),
             xattrs: Default::default(),
+            links: vec![],
         };
 
         match self.inodes.get_mut(&parent) {
@@ -559,53 +2180,330 @@ impl Filesystem for FS0X {
                         entries.push(new_inode);
                     },
                     _ => {
-                        return reply.error(libc::ENOENT);
+                        return reply.error(libc::ENOTDIR);
                     }
                 }
+                attr.last_modified = std::time::SystemTime::now();
             },
             None => {
                 return reply.error(libc::ENOENT);
             }
         }
-    
+
         self.inodes.insert(new_inode, new_attr.clone());
 
-        let f_full_path = self.get_full_path(new_inode);
-        println!("TOUCH FOLDER: {}", f_full_path);
+        let path = self.get_full_path(new_inode);
+        self.index.insert(parent, &new_attr.fname, path.clone(), new_inode);
+        let link = SymLink::new(new_attr.fname.clone(), path.clone(), format!("{:o}", new_attr.mode), new_attr.last_modified, new_attr.gid, new_attr.uid, target);
+        self.fs_tree.add_entry(Entry::SymLink(link.clone()));
+        self.deleted.remove(&path);
+        self.pending.insert(path);
 
-        // TODO: here API call to create file in the cloud
-        // the new file path requested is available in `self.get_full_path(new_inode)`
+        // On failure the entry stays pending in the local tree and goes out
+        // with the next published operations.
+        let _ = self.publish_operations(vec![Operation::Create { entry: Entry::SymLink(link) }]);
 
+        reply.entry(&TTL, &(&new_attr).into(), 0);
+    }
 
-        let new_dir = Directory::new(new_attr.fname.clone(), f_full_path, format!("{:o}", new_attr.mode), new_attr.last_modified, new_attr.gid, new_attr.uid);
-        self.fs_tree.add_entry(Entry::Directory(new_dir));
+    fn readlink(&mut self, _req: &Request, ino: u64, reply: ReplyData) {
+        match self.attributes(ino).map(|attr| attr.kind.clone()) {
+            Some(FileKind::Symlink(target)) => {
+                reply.data(target.as_bytes());
+            },
+            Some(_) => {
+                reply.error(libc::EINVAL);
+            },
+            None => {
+                reply.error(libc::ENOENT);
+            }
+        }
+    }
+
+    fn setxattr(
+        &mut self,
+        req: &Request,
+        ino: u64,
+        name: &OsStr,
+        value: &[u8],
+        flags: i32,
+        _position: u32,
+        reply: ReplyEmpty,
+    ) {
+        if Snapshots::contains(ino) {
+            return reply.error(libc::EROFS);
+        }
 
-        let fs_tree_json = serde_json::to_string(&self.fs_tree).unwrap();
-        println!("FS_TREE: {}", fs_tree_json);
+        // Names are mirrored as strings.
+        let name = match name.to_str() {
+            Some(name) => name.as_bytes().to_vec(),
+            None => {
+                return reply.error(libc::EINVAL);
+            }
+        };
+        let attr = match self.inodes.get_mut(&ino) {
+            Some(attr) => attr,
+            None => {
+                return reply.error(libc::ENOENT);
+            }
+        };
 
-        let signer = DefaultEthereumSigner::new("0xdcf2cbdd171a21c480aa7f53d77f31bb102282b3ff099c78e3118b37348c72f7".to_string()).unwrap();
-        let account_address = signer.get_address();
+        if let Err(error_code) = check_xattr_access(attr, req.uid(), req.gid(), &name, libc::W_OK) {
+            return reply.error(error_code);
+        }
 
-        let params = CreatePostRequest {
-            signer: signer,
-            channel: format!("fs0x-{}-{}", account_address, self.id),
-            custom_type: "fs_tree".to_string(),
-            item_type: sdk::common::ItemType::Inline,
-            content: fs_tree_json.clone(),
+        let exists = attr.xattrs.contains_key(&name);
+        if flags & libc::XATTR_CREATE != 0 && exists {
+            return reply.error(libc::EEXIST);
+        }
+        if flags & libc::XATTR_REPLACE != 0 && !exists {
+            return reply.error(libc::ENODATA);
+        }
+
+        attr.xattrs.insert(name, value.to_vec());
+        attr.last_metadata_changed = SystemTime::now();
+        let ops = self.update_tree_entry(ino);
+
+        // On failure the change stays pending in the local tree and goes out
+        // with the next published operations.
+        let _ = self.publish_operations(ops);
+
+        reply.ok();
+    }
+
+    fn getxattr(&mut self, req: &Request, ino: u64, name: &OsStr, size: u32, reply: ReplyXattr) {
+        let attr = match self.attributes(ino) {
+            Some(attr) => attr,
+            None => {
+                return reply.error(libc::ENOENT);
+            }
         };
-        let mut rt = Runtime::new().unwrap();
-        let res = rt.block_on(self.asdk.post().v0().create(&params));
+        let name = name.as_encoded_bytes();
 
-        match res {
-            Ok(_) => {
-                println!("FS_TREE POSTED");
+        if let Err(error_code) = check_xattr_access(&attr, req.uid(), req.gid(), name, libc::R_OK) {
+            return reply.error(error_code);
+        }
+
+        match attr.xattrs.get(name) {
+            Some(value) if size == 0 => reply.size(value.len() as u32),
+            Some(value) if value.len() <= size as usize => reply.data(value),
+            Some(_) => reply.error(libc::ERANGE),
+            None => reply.error(libc::ENODATA),
+        }
+    }
+
+    fn listxattr(&mut self, _req: &Request, ino: u64, size: u32, reply: ReplyXattr) {
+        let list = match self.attributes(ino) {
+            Some(attr) => list_xattrs(&attr.xattrs),
+            None => {
+                return reply.error(libc::ENOENT);
+            }
+        };
+
+        match size {
+            0 => reply.size(list.len() as u32),
+            _ if list.len() <= size as usize => reply.data(&list),
+            _ => reply.error(libc::ERANGE),
+        }
+    }
+
+    fn removexattr(&mut self, req: &Request, ino: u64, name: &OsStr, reply: ReplyEmpty) {
+        if Snapshots::contains(ino) {
+            return reply.error(libc::EROFS);
+        }
+
+        let attr = match self.inodes.get_mut(&ino) {
+            Some(attr) => attr,
+            None => {
+                return reply.error(libc::ENOENT);
+            }
+        };
+        let name = name.as_encoded_bytes();
+
+        if let Err(error_code) = check_xattr_access(attr, req.uid(), req.gid(), name, libc::W_OK) {
+            return reply.error(error_code);
+        }
+        if attr.xattrs.remove(name).is_none() {
+            return reply.error(libc::ENODATA);
+        }
+
+        attr.last_metadata_changed = SystemTime::now();
+        let ops = self.update_tree_entry(ino);
+
+        // On failure the change stays pending in the local tree and goes out
+        // with the next published operations.
+        let _ = self.publish_operations(ops);
+
+        reply.ok();
+    }
+
+    fn rmdir(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        if self.is_snapshot_name(parent, name) {
+            return reply.error(libc::EROFS);
+        }
+
+        let attr = match self.lookup_name(parent, name) {
+            Ok(attr) => attr,
+            Err(error_code) => {
+                return reply.error(error_code);
+            }
+        };
+
+        match &attr.kind {
+            FileKind::Directory(entries) if !entries.is_empty() => {
+                return reply.error(libc::ENOTEMPTY);
             },
-            Err(e) => {
-                println!("FS_TREE POST ERROR: {:?}", e);
+            FileKind::Directory(_) => {},
+            _ => {
+                return reply.error(libc::ENOTDIR);
             }
-            
         }
 
-        reply.entry(&TTL, &(&new_attr).into(), 0);
+        match self.delete_name(attr.inode, parent, name.to_str().unwrap()) {
+            Ok(_) => reply.ok(),
+            Err(error_code) => reply.error(error_code),
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::HttpClient;
+
+    /// Build a filesystem holding only its root, along with the receiving
+    /// end of its invalidations
+    fn test_fs() -> (FS0X, Receiver<Invalidation>) {
+        let asdk = AlephSDK::new(HttpClient::new().unwrap());
+        let mut fs = FS0X::new(asdk, DefaultEthereumSigner::random(), "test".to_string());
+        let (sender, invalidations) = mpsc::channel();
+        fs.invalidations = Some(sender);
+
+        fs.inodes.insert(fuser::FUSE_ROOT_ID, InodeAttributes {
+            inode: fuser::FUSE_ROOT_ID,
+            pinode: None,
+            fname: "".to_string(),
+            open_file_handles: 0,
+            size: 0,
+            last_accessed: SystemTime::now(),
+            last_modified: SystemTime::now(),
+            last_metadata_changed: SystemTime::now(),
+            kind: FileKind::Directory(vec![]),
+            mode: 0o777,
+            hardlinks: 2,
+            uid: 0,
+            gid: 0,
+            xattrs: Default::default(),
+            links: vec![],
+        });
+
+        (fs, invalidations)
+    }
+
+    fn tree_with(entries: Vec<Entry>) -> FSTree {
+        let mut fs_tree = FSTree::new("/".to_string());
+        for entry in entries {
+            fs_tree.add_entry(entry);
+        }
+        fs_tree
+    }
+
+    #[test]
+    fn it_applies_remote_changes_and_invalidates_them() {
+        let (mut fs, invalidations) = test_fs();
+        let now = SystemTime::now();
+        let dir = Entry::Directory(Directory::new("docs".to_string(), "/docs/".to_string(), "755".to_string(), now, 0, 0));
+        let file = File::new("a.txt".to_string(), "/docs/a.txt/".to_string(), 4, "644".to_string(), now, 0, 0);
+
+        fs.apply_fs_tree(tree_with(vec![dir.clone(), Entry::File(file.clone())]));
+
+        let attr = fs.get_inode_from_path("/docs/a.txt/").unwrap();
+        assert_eq!(attr.size, 4);
+        let dir_inode = attr.pinode.unwrap();
+        assert_eq!(invalidations.try_iter().collect::<Vec<_>>(), vec![
+            Invalidation::Entry(fuser::FUSE_ROOT_ID, "docs".to_string()),
+            Invalidation::Entry(dir_inode, "a.txt".to_string()),
+        ]);
+
+        let mut updated = file.clone();
+        updated.size = 8;
+        fs.apply_fs_tree(tree_with(vec![dir.clone(), Entry::File(updated)]));
+
+        assert_eq!(fs.inodes[&attr.inode].size, 8);
+        assert_eq!(invalidations.try_iter().collect::<Vec<_>>(), vec![Invalidation::Inode(attr.inode)]);
+
+        fs.apply_fs_tree(tree_with(vec![dir]));
+
+        assert!(fs.get_inode_from_path("/docs/a.txt/").is_none());
+        assert_eq!(invalidations.try_iter().collect::<Vec<_>>(), vec![
+            Invalidation::Entry(dir_inode, "a.txt".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn it_skips_entries_with_an_invalid_permission() {
+        let (mut fs, _invalidations) = test_fs();
+        let now = SystemTime::now();
+        let bad = File::new("bad.txt".to_string(), "/bad.txt/".to_string(), 4, "rw-r--r--".to_string(), now, 0, 0);
+        let good = File::new("good.txt".to_string(), "/good.txt/".to_string(), 4, "644".to_string(), now, 0, 0);
+
+        fs.apply_fs_tree(tree_with(vec![Entry::File(bad), Entry::File(good)]));
+
+        assert!(fs.get_inode_from_path("/bad.txt/").is_none());
+        assert_eq!(fs.get_inode_from_path("/good.txt/").unwrap().mode, 0o644);
+    }
+
+    #[test]
+    fn it_knows_the_contents_stored_by_applied_trees() {
+        let (mut fs, _invalidations) = test_fs();
+        let mut file = File::new("a.txt".to_string(), "/a.txt/".to_string(), 4, "644".to_string(), SystemTime::now(), 0, 0);
+        file.chunks = vec![Chunk { hash: "c1".to_string(), size: 4 }];
+
+        fs.apply_fs_tree(tree_with(vec![Entry::File(file)]));
+
+        assert!(fs.is_stored("c1"));
+        assert!(!fs.is_stored("c2"));
+    }
+
+    #[test]
+    fn it_serves_past_trees_under_snapshots() {
+        let (mut fs, _invalidations) = test_fs();
+        let file = Entry::File(File::new("a.txt".to_string(), "/a.txt/".to_string(), 0, "644".to_string(), SystemTime::UNIX_EPOCH, 0, 0));
+        let mut past = tree_with(vec![file]);
+        past.set_updated_at(SystemTime::UNIX_EPOCH + Duration::from_secs(60));
+        fs.snapshots.update(vec![(60, past)]);
+
+        let dir = fs.lookup_snapshot(fuser::FUSE_ROOT_ID, OsStr::new(SNAPSHOTS_DIR)).unwrap();
+        let root = fs.lookup_snapshot(dir.inode, OsStr::new("1970-01-01T00:01:00Z")).unwrap();
+        let file = fs.lookup_snapshot(root.inode, OsStr::new("a.txt")).unwrap();
+
+        assert_eq!(fs.attributes(file.inode).unwrap().pinode, Some(root.inode));
+        assert!(fs.is_snapshot_name(fuser::FUSE_ROOT_ID, OsStr::new(SNAPSHOTS_DIR)));
+        assert!(!fs.is_snapshot_name(fuser::FUSE_ROOT_ID, OsStr::new("a.txt")));
+        assert_eq!(fs.open_snapshot_file(file.inode, libc::O_RDWR), Err(libc::EROFS));
+
+        let fh = fs.open_snapshot_file(file.inode, libc::O_RDONLY).unwrap();
+        assert_eq!(fs.load_file_handle(fh, file.inode).unwrap().read(0, 16), b"");
+    }
+
+    #[test]
+    fn it_keeps_local_deletions_over_an_older_tree() {
+        let (mut fs, _invalidations) = test_fs();
+        let now = SystemTime::now();
+        let file = Entry::File(File::new("a.txt".to_string(), "/a.txt/".to_string(), 4, "644".to_string(), now, 0, 0));
+
+        fs.apply_fs_tree(tree_with(vec![file.clone()]));
+        let inode = fs.get_inode_from_path("/a.txt/").unwrap().inode;
+
+        fs.fs_tree.remove_entry("/a.txt/").unwrap();
+        fs.unlink_name(inode, fuser::FUSE_ROOT_ID, "a.txt");
+        fs.deleted.insert("/a.txt/".to_string());
+
+        // The fetched tree predates the deletion.
+        fs.apply_fs_tree(tree_with(vec![file]));
+        assert!(fs.get_inode_from_path("/a.txt/").is_none());
+
+        // Once a tree without it is fetched, the deletion is settled.
+        fs.apply_fs_tree(tree_with(vec![]));
+        assert!(fs.deleted.is_empty());
     }
-}
\ No newline at end of file
+}
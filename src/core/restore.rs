@@ -0,0 +1,137 @@
+use libc::c_int;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::runtime::Runtime;
+
+use super::snapshots::{format_time, parse_time};
+use super::sync::{fetch_fs_tree, fetch_snapshot, TreeSource};
+use super::FS0X;
+use crate::mirroring::{Diff, FSTree};
+use crate::sdk::AlephSDK;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum RestoreError {
+    #[error("invalid restore point {0}, expected a post hash, a date or a UNIX timestamp")]
+    InvalidPoint(String),
+
+    #[error("no tree found at {0}")]
+    NotFound(String),
+
+    #[error("failed to fetch the tree: {0}")]
+    Fetch(String),
+
+    #[error("failed to publish the restored tree (error {0})")]
+    Publish(c_int),
+}
+
+/// Past version of the tree to roll back to
+#[derive(Debug, PartialEq)]
+pub enum RestorePoint {
+    /// The snapshot posted with a hash
+    Hash(String),
+    /// The latest snapshot posted until a time
+    Time(SystemTime),
+}
+
+impl FromStr for RestorePoint {
+    type Err = RestoreError;
+
+    /// Parse a post hash, a date such as the names of `.snapshots`, or a
+    /// UNIX timestamp in seconds
+    fn from_str(point: &str) -> Result<Self, Self::Err> {
+        if point.len() == 64 && point.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Ok(RestorePoint::Hash(point.to_lowercase()));
+        }
+        if let Ok(secs) = point.parse::<u64>() {
+            return Ok(RestorePoint::Time(UNIX_EPOCH + Duration::from_secs(secs)));
+        }
+
+        parse_time(point)
+            .map(RestorePoint::Time)
+            .ok_or_else(|| RestoreError::InvalidPoint(point.to_string()))
+    }
+}
+
+impl fmt::Display for RestorePoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestorePoint::Hash(hash) => write!(f, "{}", hash),
+            RestorePoint::Time(time) => write!(f, "{}", format_time(*time)),
+        }
+    }
+}
+
+/// Fetch the tree published on a channel at a restore point, or its latest
+/// version without one
+pub fn fetch_tree(rt: &Runtime, asdk: &AlephSDK, source: &TreeSource, point: Option<&str>) -> Result<FSTree, RestoreError> {
+    match point {
+        Some(point) => {
+            let point: RestorePoint = point.parse()?;
+            rt.block_on(fetch_snapshot(asdk, source, &point))
+                .map_err(|e| RestoreError::Fetch(e.to_string()))?
+                .ok_or_else(|| RestoreError::NotFound(point.to_string()))
+        },
+        None => rt.block_on(fetch_fs_tree(asdk, source))
+            .map_err(|e| RestoreError::Fetch(e.to_string()))?
+            .map(|(fs_tree, _)| fs_tree)
+            .ok_or_else(|| RestoreError::NotFound("the latest version".to_string())),
+    }
+}
+
+impl FS0X {
+    /// Roll the published tree back to a past snapshot
+    ///
+    /// The changes from the current tree are published as a new batch of
+    /// operations followed by a snapshot, the history stays on the channel.
+    /// On a dry run, the changes are only returned.
+    pub fn restore(&mut self, point: &str, dry_run: bool) -> Result<Diff, RestoreError> {
+        let point: RestorePoint = point.parse()?;
+        let source = self.source();
+
+        let past = self.rt.block_on(fetch_snapshot(&self.asdk, &source, &point))
+            .map_err(|e| RestoreError::Fetch(e.to_string()))?
+            .ok_or_else(|| RestoreError::NotFound(point.to_string()))?;
+        // Nothing published yet, the past tree is restored from scratch.
+        let current = self.rt.block_on(fetch_fs_tree(&self.asdk, &source))
+            .map_err(|e| RestoreError::Fetch(e.to_string()))?
+            .map(|(fs_tree, _)| fs_tree)
+            .unwrap_or_else(|| FSTree::new("/".to_string()));
+
+        let diff = Diff::between(&current, &past);
+        if dry_run || diff.is_empty() {
+            return Ok(diff);
+        }
+
+        self.fs_tree = current;
+        let ops = diff.operations();
+        for op in &ops {
+            if let Err(e) = op.apply(&mut self.fs_tree) {
+                eprintln!("skipping operation {:?}: {}", op, e);
+            }
+        }
+
+        self.publish_operations(ops)
+            .and_then(|_| self.publish_fs_tree())
+            .and_then(|_| self.wait_for_confirmation())
+            .map_err(RestoreError::Publish)?;
+
+        Ok(diff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_restore_points() {
+        let hash = "73475CB40A568E8DA8A045CED110137E159F890AC4DA883B6B17DC651B3A8049";
+
+        assert_eq!(hash.parse(), Ok(RestorePoint::Hash(hash.to_lowercase())));
+        assert_eq!("60".parse(), Ok(RestorePoint::Time(UNIX_EPOCH + Duration::from_secs(60))));
+        assert_eq!("1970-01-01T00:01:00Z".parse(), Ok(RestorePoint::Time(UNIX_EPOCH + Duration::from_secs(60))));
+        assert_eq!("yesterday".parse::<RestorePoint>(), Err(RestoreError::InvalidPoint("yesterday".to_string())));
+    }
+}
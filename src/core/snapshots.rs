@@ -0,0 +1,371 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::inode::{FileKind, Inode, InodeAttributes};
+use super::xattr::decode_xattrs;
+use crate::mirroring::{Entry, FSTree};
+
+/// Name of the directory listing the past versions of the tree, at the root
+/// of the mount
+pub const SNAPSHOTS_DIR: &str = ".snapshots";
+
+/// Inode of the `.snapshots` directory, the inodes of its content follow
+///
+/// It is far above the inodes of the mirrored entries, which are allocated
+/// from the root up.
+pub const SNAPSHOTS_INODE: Inode = 1 << 48;
+
+/// How long the list of past versions is kept before being fetched again
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Past versions of the tree, browsable read-only under `.snapshots`
+///
+/// Each snapshot of the tree posted on the channel is a directory named by
+/// the time of the snapshot. Inodes are given to the entries of the past
+/// trees as they are looked up.
+pub struct Snapshots {
+    /// Past trees by name, oldest first
+    trees: BTreeMap<String, FSTree>,
+    /// Snapshot name and path of the entries given an inode, the path of
+    /// the root of a snapshot being `/`
+    locations: BTreeMap<Inode, (String, String)>,
+    inodes: BTreeMap<(String, String), Inode>,
+    /// When the past versions were last fetched
+    fetched_at: Option<SystemTime>,
+    /// Post time of the latest snapshot fetched, in seconds
+    latest: i64,
+}
+
+impl Snapshots {
+    pub fn new() -> Self {
+        Self {
+            trees: BTreeMap::new(),
+            locations: BTreeMap::new(),
+            inodes: BTreeMap::new(),
+            fetched_at: None,
+            latest: 0,
+        }
+    }
+
+    /// Whether an inode is `.snapshots` or lives below it
+    pub fn contains(inode: Inode) -> bool {
+        inode >= SNAPSHOTS_INODE
+    }
+
+    /// Whether the past versions should be fetched again
+    pub fn is_stale(&self) -> bool {
+        self.fetched_at.is_none_or(|fetched_at| fetched_at.elapsed().unwrap_or(REFRESH_INTERVAL) >= REFRESH_INTERVAL)
+    }
+
+    /// Post time of the latest snapshot known, in seconds, later ones are
+    /// the ones to fetch
+    pub fn since(&self) -> i64 {
+        self.latest
+    }
+
+    /// Add the snapshots fetched from the channel, with their post time in
+    /// seconds
+    ///
+    /// Snapshots are named by the time of the last change they saw.
+    pub fn update(&mut self, snapshots: Vec<(i64, FSTree)>) {
+        for (time, fs_tree) in snapshots {
+            let taken_at = match fs_tree.updated_at() {
+                UNIX_EPOCH => UNIX_EPOCH + Duration::from_secs(time.max(0) as u64),
+                updated_at => updated_at,
+            };
+
+            self.latest = self.latest.max(time);
+            self.trees.insert(format_time(taken_at), fs_tree);
+        }
+
+        self.fetched_at = Some(SystemTime::now());
+    }
+
+    /// Mark the past versions as fetched, after a failed fetch
+    pub fn touch(&mut self) {
+        self.fetched_at = Some(SystemTime::now());
+    }
+
+    /// Attributes of a virtual inode
+    ///
+    /// Everything is read-only, write permissions are dropped.
+    pub fn attributes(&self, inode: Inode) -> Option<InodeAttributes> {
+        if inode == SNAPSHOTS_INODE {
+            let modified_at = self.trees.values().last().map(FSTree::updated_at).unwrap_or(UNIX_EPOCH);
+            return Some(directory(inode, fuser::FUSE_ROOT_ID, SNAPSHOTS_DIR, modified_at));
+        }
+
+        let (snapshot, path) = self.locations.get(&inode)?;
+        let fs_tree = self.trees.get(snapshot)?;
+        if path == "/" {
+            return Some(directory(inode, SNAPSHOTS_INODE, snapshot, fs_tree.updated_at()));
+        }
+
+        let entry = fs_tree.get_entry(path)?;
+        let pinode = self.inodes.get(&(snapshot.clone(), parent_path(path))).copied();
+        let (uid, gid) = entry.owner();
+        let modified_at = entry.modified_at();
+        let (kind, size) = match entry {
+            Entry::File(file) => (FileKind::File, file.size),
+            Entry::Directory(_) => (FileKind::Directory(vec![]), 0),
+            Entry::SymLink(link) => (FileKind::Symlink(link.link_to.clone()), link.link_to.len() as u64),
+        };
+
+        Some(InodeAttributes {
+            inode,
+            pinode,
+            fname: entry.name().to_string(),
+            open_file_handles: 0,
+            size,
+            last_accessed: modified_at,
+            last_modified: modified_at,
+            last_metadata_changed: modified_at,
+            hardlinks: if matches!(kind, FileKind::Directory(_)) { 2 } else { 1 },
+            kind,
+            mode: u16::from_str_radix(entry.permission(), 8).unwrap_or(0o444) & !0o222,
+            uid,
+            gid,
+            xattrs: decode_xattrs(entry.xattrs()),
+            links: vec![],
+        })
+    }
+
+    /// Entry of the past tree a virtual inode points to
+    pub fn entry(&self, inode: Inode) -> Option<&Entry> {
+        let (snapshot, path) = self.locations.get(&inode)?;
+
+        self.trees.get(snapshot)?.get_entry(path)
+    }
+
+    /// Lookup a snapshot, or an entry of a snapshot, by name
+    pub fn lookup(&mut self, parent: Inode, name: &str) -> Option<InodeAttributes> {
+        let (snapshot, path) = match parent {
+            SNAPSHOTS_INODE => (name.to_string(), "/".to_string()),
+            _ => {
+                let (snapshot, path) = self.locations.get(&parent)?;
+                (snapshot.clone(), format!("{}{}/", path, name))
+            }
+        };
+
+        let fs_tree = self.trees.get(&snapshot)?;
+        if path != "/" && fs_tree.get_entry(&path).is_none() {
+            return None;
+        }
+
+        let inode = self.inode(snapshot, path);
+        self.attributes(inode)
+    }
+
+    /// Inodes, kinds and names of the content of a virtual directory
+    pub fn children(&mut self, inode: Inode) -> Option<Vec<(Inode, fuser::FileType, String)>> {
+        let names: Vec<(String, String, fuser::FileType)> = match inode {
+            SNAPSHOTS_INODE => self.trees.keys()
+                .map(|snapshot| (snapshot.clone(), "/".to_string(), fuser::FileType::Directory))
+                .collect(),
+            _ => {
+                let (snapshot, path) = self.locations.get(&inode)?;
+                let fs_tree = self.trees.get(snapshot)?;
+                if path != "/" && !matches!(fs_tree.get_entry(path), Some(Entry::Directory(_))) {
+                    return None;
+                }
+
+                fs_tree.get_children(path).into_iter()
+                    .map(|entry| (snapshot.clone(), entry.path().to_string(), file_type(entry)))
+                    .collect()
+            }
+        };
+
+        let children = names.into_iter()
+            .map(|(snapshot, path, kind)| {
+                let name = match path.as_str() {
+                    "/" => snapshot.clone(),
+                    _ => path.trim_end_matches('/').rsplit('/').next().unwrap_or_default().to_string(),
+                };
+
+                (self.inode(snapshot, path), kind, name)
+            })
+            .collect();
+
+        Some(children)
+    }
+
+    /// Inode of an entry of a snapshot, given on first use
+    fn inode(&mut self, snapshot: String, path: String) -> Inode {
+        let key = (snapshot, path);
+        if let Some(inode) = self.inodes.get(&key) {
+            return *inode;
+        }
+
+        let inode = SNAPSHOTS_INODE + 1 + self.locations.len() as Inode;
+        self.locations.insert(inode, key.clone());
+        self.inodes.insert(key, inode);
+
+        inode
+    }
+}
+
+fn directory(inode: Inode, pinode: Inode, name: &str, modified_at: SystemTime) -> InodeAttributes {
+    InodeAttributes {
+        inode,
+        pinode: Some(pinode),
+        fname: name.to_string(),
+        open_file_handles: 0,
+        size: 0,
+        last_accessed: modified_at,
+        last_modified: modified_at,
+        last_metadata_changed: modified_at,
+        kind: FileKind::Directory(vec![]),
+        mode: 0o555,
+        hardlinks: 2,
+        uid: 0,
+        gid: 0,
+        xattrs: Default::default(),
+        links: vec![],
+    }
+}
+
+fn file_type(entry: &Entry) -> fuser::FileType {
+    match entry {
+        Entry::File(_) => fuser::FileType::RegularFile,
+        Entry::Directory(_) => fuser::FileType::Directory,
+        Entry::SymLink(_) => fuser::FileType::Symlink,
+    }
+}
+
+/// Path of the directory holding an entry, `/` for the root
+fn parent_path(path: &str) -> String {
+    match path.trim_end_matches('/').rfind('/') {
+        Some(end) => path[..=end].to_string(),
+        None => "/".to_string(),
+    }
+}
+
+/// Format a time as a UTC ISO 8601 date, such as `2024-03-01T10:00:00Z`
+///
+/// Milliseconds are kept when there are some, as in `2024-03-01T10:00:00.250Z`.
+pub fn format_time(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let (secs, millis) = (since_epoch.as_secs(), since_epoch.subsec_millis());
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+
+    // Civil date from the days since the epoch, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let date = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year, month, day, secs_of_day / 3600, secs_of_day % 3600 / 60, secs_of_day % 60,
+    );
+    match millis {
+        0 => format!("{date}Z"),
+        millis => format!("{date}.{millis:03}Z"),
+    }
+}
+
+/// Parse a UTC ISO 8601 date formatted by `format_time`
+pub fn parse_time(date: &str) -> Option<SystemTime> {
+    let date = date.strip_suffix('Z')?;
+    let (day, time) = date.split_once('T')?;
+    let (time, millis) = match time.split_once('.') {
+        Some((time, millis)) if millis.len() == 3 => (time, millis.parse::<u64>().ok()?),
+        Some(_) => return None,
+        None => (time, 0),
+    };
+
+    let mut day = day.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (day.next()?.ok()?, day.next()?.ok()?, day.next()?.ok()?);
+    let mut time = time.splitn(3, ':').map(str::parse::<u64>);
+    let (hours, minutes, seconds) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hours > 23 || minutes > 59 || seconds > 59 {
+        return None;
+    }
+
+    // Days since the epoch from a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = u64::try_from(era * 146097 + doe - 719468).ok()?;
+
+    Some(UNIX_EPOCH + Duration::from_secs(days * 86400 + hours * 3600 + minutes * 60 + seconds) + Duration::from_millis(millis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirroring::{Directory, File};
+
+    #[test]
+    fn it_formats_times_as_utc_dates() {
+        assert_eq!(format_time(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(format_time(UNIX_EPOCH + Duration::from_secs(1709287200)), "2024-03-01T10:00:00Z");
+        assert_eq!(format_time(UNIX_EPOCH + Duration::from_secs(951868799)), "2000-02-29T23:59:59Z");
+        assert_eq!(format_time(UNIX_EPOCH + Duration::from_millis(1709287200250)), "2024-03-01T10:00:00.250Z");
+    }
+
+    #[test]
+    fn it_parses_the_dates_it_formats() {
+        for millis in [0, 951868799000, 1709287200250, 4102444800001] {
+            let time = UNIX_EPOCH + Duration::from_millis(millis);
+            assert_eq!(parse_time(&format_time(time)), Some(time));
+        }
+
+        assert_eq!(parse_time("2024-13-01T10:00:00Z"), None);
+        assert_eq!(parse_time("2024-03-01"), None);
+        assert_eq!(parse_time("2024-03-01T10:00:00.5Z"), None);
+    }
+
+    #[test]
+    fn it_browses_past_trees_read_only() {
+        let at = UNIX_EPOCH + Duration::from_secs(1709287200);
+        let mut fs_tree = FSTree::new("/".to_string());
+        fs_tree.add_entry(Entry::Directory(Directory::new("docs".to_string(), "/docs/".to_string(), "755".to_string(), at, 0, 0)));
+        fs_tree.add_entry(Entry::File(File::new("a.txt".to_string(), "/docs/a.txt/".to_string(), 4, "644".to_string(), at, 0, 0)));
+        fs_tree.set_updated_at(at);
+
+        let mut snapshots = Snapshots::new();
+        snapshots.update(vec![(1709287201, fs_tree)]);
+
+        let listed = snapshots.children(SNAPSHOTS_INODE).unwrap();
+        assert_eq!(listed[0].2, "2024-03-01T10:00:00Z");
+
+        let root = snapshots.lookup(SNAPSHOTS_INODE, "2024-03-01T10:00:00Z").unwrap();
+        let docs = snapshots.lookup(root.inode, "docs").unwrap();
+        let file = snapshots.lookup(docs.inode, "a.txt").unwrap();
+
+        assert!(Snapshots::contains(file.inode));
+        assert_eq!(file.pinode, Some(docs.inode));
+        assert_eq!(file.size, 4);
+        assert_eq!(file.mode, 0o444);
+        assert_eq!(snapshots.children(docs.inode).unwrap(), vec![(file.inode, fuser::FileType::RegularFile, "a.txt".to_string())]);
+        assert!(snapshots.lookup(docs.inode, "missing").is_none());
+        assert_eq!(snapshots.since(), 1709287201);
+    }
+
+    #[test]
+    fn it_keeps_snapshots_taken_within_a_second_apart() {
+        let at = UNIX_EPOCH + Duration::from_secs(1709287200);
+        let mut first = FSTree::new("/".to_string());
+        first.set_updated_at(at + Duration::from_millis(100));
+        let mut second = FSTree::new("/".to_string());
+        second.add_entry(Entry::Directory(Directory::new("docs".to_string(), "/docs/".to_string(), "755".to_string(), at, 0, 0)));
+        second.set_updated_at(at + Duration::from_millis(600));
+
+        let mut snapshots = Snapshots::new();
+        snapshots.update(vec![(1709287200, first), (1709287200, second)]);
+
+        let names: Vec<String> = snapshots.children(SNAPSHOTS_INODE).unwrap().into_iter().map(|(_, _, name)| name).collect();
+        assert_eq!(names, vec!["2024-03-01T10:00:00.100Z", "2024-03-01T10:00:00.600Z"]);
+    }
+}
@@ -0,0 +1,99 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+/// Local copy of the file contents written but not uploaded yet
+///
+/// Each content is stored as `<key>.data`, with the path of its file in
+/// `<key>.path`, so that writes survive a crash or an unmount while the Aleph
+/// node cannot be reached.
+pub struct Spool {
+    dir: PathBuf,
+}
+
+impl Spool {
+    /// Open the spool stored in `dir`, creating the directory if needed
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// Store the content of the file at `path`, replacing any previous one
+    pub fn save(&self, path: &str, data: &[u8]) -> io::Result<()> {
+        let (data_path, path_path) = self.paths(path);
+
+        // The path is written last, a content without it is ignored.
+        fs::write(&data_path, data)?;
+        fs::File::open(&data_path)?.sync_all()?;
+        fs::write(&path_path, path)?;
+
+        Ok(())
+    }
+
+    /// Drop the content of the file at `path` once uploaded
+    pub fn remove(&self, path: &str) {
+        let (data_path, path_path) = self.paths(path);
+
+        let _ = fs::remove_file(path_path);
+        let _ = fs::remove_file(data_path);
+    }
+
+    /// Contents left by a previous mount, with the path of their file
+    pub fn load(&self) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let mut contents = Vec::new();
+
+        for dir_entry in fs::read_dir(&self.dir)? {
+            let path_path = dir_entry?.path();
+            if path_path.extension().is_none_or(|extension| extension != "path") {
+                continue;
+            }
+
+            let path = fs::read_to_string(&path_path)?;
+            match fs::read(path_path.with_extension("data")) {
+                Ok(data) => contents.push((path, data)),
+                Err(e) => eprintln!("dropping spooled content of {}: {}", path, e),
+            }
+        }
+        contents.sort();
+
+        Ok(contents)
+    }
+
+    /// Location of the content and the path of a file
+    fn paths(&self, path: &str) -> (PathBuf, PathBuf) {
+        let key = hex::encode(Sha256::digest(path.as_bytes()));
+
+        (self.dir.join(format!("{}.data", key)), self.dir.join(format!("{}.path", key)))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_keeps_contents_until_removed() {
+        let dir = std::env::temp_dir().join(format!("fs0x-spool-{}", std::process::id()));
+        let spool = Spool::open(&dir).unwrap();
+
+        spool.save("/docs/a.txt/", b"draft").unwrap();
+        spool.save("/docs/a.txt/", b"final").unwrap();
+        spool.save("/b.txt/", b"other").unwrap();
+        spool.remove("/b.txt/");
+
+        let contents = Spool::open(&dir).unwrap().load().unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(contents, vec![("/docs/a.txt/".to_string(), b"final".to_vec())]);
+    }
+}
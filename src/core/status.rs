@@ -0,0 +1,88 @@
+use std::fmt;
+use std::time::UNIX_EPOCH;
+use tokio::runtime::Runtime;
+
+use super::sync::{fetch_fs_tree, TreeSource};
+use crate::mirroring::{Conflict, FSTree};
+use crate::sdk::post::v0::PostSDKV0Error;
+use crate::sdk::AlephSDK;
+
+/// State of the FSTree published on a channel, as shown by `fs0x status`
+pub struct Status {
+    channel: String,
+    fs_tree: FSTree,
+    conflicts: Vec<Conflict>,
+}
+
+impl Status {
+    /// Fetch the FSTree published on a channel
+    ///
+    /// Returns `None` when nothing was published yet.
+    pub fn fetch(rt: &Runtime, asdk: &AlephSDK, source: &TreeSource) -> Result<Option<Self>, PostSDKV0Error> {
+        let fetched = rt.block_on(fetch_fs_tree(asdk, source))?;
+
+        Ok(fetched.map(|(fs_tree, conflicts)| Self {
+            channel: source.channel.clone(),
+            fs_tree,
+            conflicts,
+        }))
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let updated_at = self.fs_tree.updated_at().duration_since(UNIX_EPOCH).unwrap_or_default();
+
+        writeln!(f, "channel: {}", self.channel)?;
+        writeln!(
+            f,
+            "entries: {} files, {} directories, {} symlinks",
+            self.fs_tree.get_files().len(),
+            self.fs_tree.get_dirs().len(),
+            self.fs_tree.get_symlinks().len(),
+        )?;
+        writeln!(f, "used: {} bytes", self.fs_tree.used_bytes())?;
+        writeln!(f, "updated at: {}", updated_at.as_secs())?;
+
+        writeln!(f, "peers:")?;
+        for (author, seq) in self.fs_tree.heads() {
+            writeln!(f, "  {} at batch {}", author, seq)?;
+        }
+
+        writeln!(f, "conflicts: {}", self.conflicts.len())?;
+        for conflict in &self.conflicts {
+            writeln!(f, "  {}", conflict)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirroring::{Entry, File};
+    use std::time::SystemTime;
+
+    #[test]
+    fn it_reports_the_tree_and_its_conflicts() {
+        let mut fs_tree = FSTree::new("/".to_string());
+        fs_tree.add_entry(Entry::File(File::new("a.txt".to_string(), "/a.txt/".to_string(), 4, "644".to_string(), SystemTime::now(), 0, 0)));
+        fs_tree.set_head("0xa", 2);
+
+        let status = Status {
+            channel: "fs0x-0xa-docs".to_string(),
+            fs_tree,
+            conflicts: vec![Conflict {
+                path: "/a.txt/".to_string(),
+                author: "0xb".to_string(),
+                kept_at: None,
+            }],
+        };
+        let report = status.to_string();
+
+        assert!(report.contains("entries: 1 files, 0 directories, 0 symlinks"));
+        assert!(report.contains("  0xa at batch 2"));
+        assert!(report.contains("  concurrent deletion of /a.txt/ by 0xb dropped"));
+    }
+}
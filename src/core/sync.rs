@@ -0,0 +1,326 @@
+use fuser::Notifier;
+use std::ffi::OsStr;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::runtime::Handle;
+
+use super::inode::Inode;
+use super::restore::RestorePoint;
+use crate::crypto::{open_text, Cipher};
+use crate::mirroring::{replay, Conflict, FSTree, OperationBatch};
+use crate::sdk::post::v0::req_objects::ListPostsRequest;
+use crate::sdk::post::v0::PostSDKV0Error;
+use crate::sdk::post::v0::res_objects::PostMessage;
+use crate::sdk::AlephSDK;
+
+/// Change to a local inode the kernel may hold in its caches
+#[derive(Debug, PartialEq)]
+pub enum Invalidation {
+    /// A name appeared or went away in a directory
+    Entry(Inode, String),
+    /// The content or attributes of an inode changed
+    Inode(Inode),
+}
+
+/// Post type of the snapshots of the FSTree
+pub const SNAPSHOT_TYPE: &str = "fs_tree";
+
+/// Post type of the batches of operations made on the FSTree
+pub const OPERATIONS_TYPE: &str = "fs_op";
+
+/// Number of posts fetched per page when replaying operations
+const OPERATIONS_PAGE_SIZE: u32 = 200;
+
+/// Number of latest snapshots fetched to find one from a trusted sender
+const SNAPSHOT_CANDIDATES: u32 = 20;
+
+/// Number of posts fetched per page when listing the past snapshots
+const SNAPSHOTS_PAGE_SIZE: u32 = 50;
+
+/// How long before the latest snapshot operations are fetched again, to
+/// catch the ones made concurrently with it
+const CONCURRENCY_WINDOW: Duration = Duration::from_secs(3600);
+
+/// Channel a FSTree is published on, with what is needed to read it
+#[derive(Clone)]
+pub struct TreeSource {
+    pub channel: String,
+    /// Addresses whose posts are trusted
+    pub senders: Vec<String>,
+    /// Key the posts are encrypted with, if any
+    pub cipher: Option<Cipher>,
+}
+
+/// Fetch the latest FSTree published on a channel
+///
+/// The latest snapshot is brought up to date by replaying the operations
+/// it did not see, merging the concurrent ones. Returns the tree with the
+/// conflicts met, `None` when nothing was published yet.
+///
+/// Only the posts signed by one of the trusted senders are taken into
+/// account.
+pub async fn fetch_fs_tree(asdk: &AlephSDK, source: &TreeSource) -> Result<Option<(FSTree, Vec<Conflict>)>, PostSDKV0Error> {
+    let TreeSource { channel, senders, cipher } = source;
+    let cipher = cipher.as_ref();
+
+    let params = ListPostsRequest::default()
+        .with_channels(vec![channel.to_string()])
+        .with_custom_types(vec![SNAPSHOT_TYPE.to_string()])
+        .with_pagination(SNAPSHOT_CANDIDATES);
+
+    let res = asdk.post().v0().list(params).await?;
+
+    let snapshot = res.posts.iter().find(|post| is_trusted(post, senders)).and_then(|post| {
+        match serde_json::from_str::<FSTree>(&read_content(post, cipher)?) {
+            Ok(fs_tree) => Some(fs_tree),
+            Err(e) => {
+                eprintln!("ignoring malformed tree {}: {}", post.item_hash, e);
+                None
+            }
+        }
+    });
+    let found = snapshot.is_some();
+    let mut fs_tree = snapshot.unwrap_or_else(|| FSTree::new("/".to_string()));
+
+    let since = fs_tree.updated_at().checked_sub(CONCURRENCY_WINDOW).unwrap_or(UNIX_EPOCH);
+    let batches = fetch_operations(asdk, channel, since, senders, cipher).await?;
+    if !found && batches.is_empty() {
+        return Ok(None);
+    }
+
+    let conflicts = replay(&mut fs_tree, batches);
+    for conflict in &conflicts {
+        eprintln!("{}: {}", channel, conflict);
+    }
+
+    Ok(Some((fs_tree, conflicts)))
+}
+
+/// Fetch the batches of operations posted from `since`
+///
+/// Fails when any page fails, as replaying only part of the operations
+/// would yield a tree nobody saw.
+async fn fetch_operations(
+    asdk: &AlephSDK,
+    channel: &str,
+    since: SystemTime,
+    senders: &[String],
+    cipher: Option<&Cipher>,
+) -> Result<Vec<OperationBatch>, PostSDKV0Error> {
+    // Posts are timestamped to the second, the batches carry the exact time.
+    let start_date = since.duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let mut batches = Vec::new();
+
+    for page in 1.. {
+        let params = ListPostsRequest::default()
+            .with_channels(vec![channel.to_string()])
+            .with_custom_types(vec![OPERATIONS_TYPE.to_string()])
+            .with_start_date(start_date)
+            .with_pagination(OPERATIONS_PAGE_SIZE)
+            .with_page(page);
+
+        let res = asdk.post().v0().list(params).await?;
+
+        let count = res.posts.len();
+        for post in res.posts.iter().filter(|post| is_trusted(post, senders)) {
+            let content = match read_content(post, cipher) {
+                Some(content) => content,
+                None => {
+                    continue;
+                }
+            };
+
+            match serde_json::from_str::<OperationBatch>(&content) {
+                // A peer may only post operations in its own name.
+                Ok(batch) if !batch.author.eq_ignore_ascii_case(&post.sender) => {
+                    eprintln!("warning: rejecting operations {} of {} posted by {}", post.item_hash, batch.author, post.sender);
+                },
+                Ok(batch) => batches.push(batch),
+                Err(e) => eprintln!("ignoring malformed operations {}: {}", post.item_hash, e),
+            }
+        }
+
+        if count < OPERATIONS_PAGE_SIZE as usize {
+            break;
+        }
+    }
+
+    Ok(batches)
+}
+
+/// Fetch the snapshots of the FSTree posted from `since`, in seconds
+///
+/// Returns the trees with their post time, `None` when the fetch failed.
+pub async fn fetch_snapshots(asdk: &AlephSDK, source: &TreeSource, since: i64) -> Option<Vec<(i64, FSTree)>> {
+    // Posts made while paging would shift the pages, they are left to the
+    // next fetch.
+    let end_date = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let mut snapshots = Vec::new();
+
+    for page in 1.. {
+        let params = ListPostsRequest::default()
+            .with_channels(vec![source.channel.clone()])
+            .with_custom_types(vec![SNAPSHOT_TYPE.to_string()])
+            .with_start_date(since)
+            .with_end_date(end_date)
+            .with_pagination(SNAPSHOTS_PAGE_SIZE)
+            .with_page(page);
+
+        let res = match asdk.post().v0().list(params).await {
+            Ok(res) => res,
+            Err(e) => {
+                eprintln!("failed to fetch the snapshots of {}: {}", source.channel, e);
+                return None;
+            }
+        };
+
+        let count = res.posts.len();
+        for post in res.posts.iter().filter(|post| is_trusted(post, &source.senders)) {
+            let content = match read_content(post, source.cipher.as_ref()) {
+                Some(content) => content,
+                None => {
+                    continue;
+                }
+            };
+
+            match serde_json::from_str::<FSTree>(&content) {
+                Ok(fs_tree) => snapshots.push((post.time as i64, fs_tree)),
+                Err(e) => eprintln!("ignoring malformed tree {}: {}", post.item_hash, e),
+            }
+        }
+
+        if count < SNAPSHOTS_PAGE_SIZE as usize {
+            break;
+        }
+    }
+
+    Some(snapshots)
+}
+
+/// Fetch the snapshot of the FSTree posted with a hash, or the latest one
+/// posted until a time
+///
+/// Returns `None` when no such snapshot was found.
+pub async fn fetch_snapshot(asdk: &AlephSDK, source: &TreeSource, point: &RestorePoint) -> Result<Option<FSTree>, PostSDKV0Error> {
+    let params = ListPostsRequest::default()
+        .with_channels(vec![source.channel.clone()])
+        .with_custom_types(vec![SNAPSHOT_TYPE.to_string()])
+        .with_pagination(SNAPSHOT_CANDIDATES);
+    let params = match point {
+        RestorePoint::Hash(hash) => params.with_hashes(vec![hash.clone()]),
+        RestorePoint::Time(time) => params.with_end_date(time.duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)),
+    };
+
+    let res = asdk.post().v0().list(params).await?;
+
+    Ok(res.posts.iter()
+        .filter(|post| is_trusted(post, &source.senders))
+        .filter_map(|post| read_content(post, source.cipher.as_ref()))
+        .find_map(|content| serde_json::from_str::<FSTree>(&content).ok()))
+}
+
+/// Whether a post was signed by one of the trusted senders
+///
+/// Rejected posts are logged, they may come from anyone knowing the name
+/// of the channel.
+fn is_trusted(post: &PostMessage<String>, senders: &[String]) -> bool {
+    if !senders.iter().any(|sender| sender.eq_ignore_ascii_case(&post.sender)) {
+        eprintln!("warning: rejecting post {} from untrusted sender {}", post.item_hash, post.sender);
+        return false;
+    }
+
+    match post.verify() {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("warning: rejecting post {}: {}", post.item_hash, e);
+            false
+        }
+    }
+}
+
+/// Read the content of a post, decrypting it if needed
+///
+/// Posts that cannot be decrypted are logged and skipped.
+fn read_content(post: &PostMessage<String>, cipher: Option<&Cipher>) -> Option<String> {
+    match open_text(cipher, &post.content) {
+        Ok(content) => Some(content),
+        Err(e) => {
+            eprintln!("ignoring post {}: {}", post.item_hash, e);
+            None
+        }
+    }
+}
+
+/// Background task fetching the FSTree published by peers
+///
+/// Fetched trees are handed over to the filesystem, which answers with the
+/// invalidations the worker forwards to the kernel. This keeps the network
+/// and the kernel notifications out of the FUSE request handlers.
+pub struct SyncWorker {
+    asdk: AlephSDK,
+    rt: Handle,
+    source: TreeSource,
+    interval: Duration,
+    trees: Sender<FSTree>,
+    invalidations: Receiver<Invalidation>,
+}
+
+impl SyncWorker {
+    pub fn new(
+        asdk: AlephSDK,
+        rt: Handle,
+        source: TreeSource,
+        interval: Duration,
+        trees: Sender<FSTree>,
+        invalidations: Receiver<Invalidation>,
+    ) -> Self {
+        Self {
+            asdk,
+            rt,
+            source,
+            interval,
+            trees,
+            invalidations,
+        }
+    }
+
+    /// Run the worker on its own thread until the filesystem goes away
+    pub fn spawn(self, notifier: Notifier) -> JoinHandle<()> {
+        thread::spawn(move || self.run(notifier))
+    }
+
+    fn run(self, notifier: Notifier) {
+        let mut next_fetch = Instant::now();
+
+        loop {
+            let timeout = next_fetch.saturating_duration_since(Instant::now());
+
+            // The kernel answers ENOENT for entries it does not cache, which
+            // is fine to ignore.
+            match self.invalidations.recv_timeout(timeout) {
+                Ok(Invalidation::Entry(parent, name)) => {
+                    let _ = notifier.inval_entry(parent, OsStr::new(&name));
+                },
+                Ok(Invalidation::Inode(inode)) => {
+                    let _ = notifier.inval_inode(inode, 0, 0);
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    match self.rt.block_on(fetch_fs_tree(&self.asdk, &self.source)) {
+                        Ok(Some((fs_tree, _))) => {
+                            if self.trees.send(fs_tree).is_err() {
+                                return;
+                            }
+                        },
+                        Ok(None) => {},
+                        Err(e) => eprintln!("failed to fetch the tree of {}: {}", self.source.channel, e),
+                    }
+                    next_fetch = Instant::now() + self.interval;
+                },
+                Err(RecvTimeoutError::Disconnected) => {
+                    return;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use super::local::{changed_content, local_path, LocalError};
+use super::FS0X;
+use crate::mirroring::{read_local_entry, Change, Diff, FSTree, Operation};
+
+/// Size of the buffer inotify events are read into
+const EVENTS_BUFFER_SIZE: usize = 4096;
+
+/// Changes to the entries of a directory that are published
+fn watch_mask() -> WatchMask {
+    WatchMask::CREATE
+        | WatchMask::MODIFY
+        | WatchMask::CLOSE_WRITE
+        | WatchMask::ATTRIB
+        | WatchMask::MOVED_FROM
+        | WatchMask::MOVED_TO
+        | WatchMask::DELETE
+        | WatchMask::DELETE_SELF
+        | WatchMask::DONT_FOLLOW
+}
+
+/// An inotify event, detached from the buffer it was read into
+struct WatchEvent {
+    wd: WatchDescriptor,
+    mask: EventMask,
+    cookie: u32,
+    name: Option<String>,
+}
+
+/// Paths of the tree touched by a batch of events
+#[derive(Debug, Default, PartialEq)]
+struct Touched {
+    /// Entries moved within the directory, by their previous and new path
+    renames: Vec<(String, String)>,
+    /// Entries to read again along with the entries below them
+    subtrees: BTreeSet<String>,
+    /// Directories whose own attributes may have changed
+    dirs: BTreeSet<String>,
+    /// Whether events were lost, the whole directory has to be read again
+    overflow: bool,
+}
+
+impl Touched {
+    /// Gather the paths touched by events, following the watched directories
+    /// as they are moved or removed
+    fn from_events(events: &[WatchEvent], watched: &mut HashMap<WatchDescriptor, String>) -> Self {
+        let mut touched = Touched::default();
+        let mut moved_from = HashMap::new();
+
+        for event in events {
+            if event.mask.contains(EventMask::Q_OVERFLOW) {
+                touched.overflow = true;
+                continue;
+            }
+            let dir = match watched.get(&event.wd) {
+                Some(dir) => dir.clone(),
+                None => continue,
+            };
+            if event.mask.contains(EventMask::IGNORED) {
+                watched.remove(&event.wd);
+                continue;
+            }
+            // The root directory is not an entry of the tree.
+            if dir != "/" {
+                touched.dirs.insert(dir.clone());
+            }
+
+            let path = match &event.name {
+                Some(name) => format!("{}{}/", dir, name),
+                None => continue,
+            };
+            if event.mask.contains(EventMask::MOVED_FROM) {
+                moved_from.insert(event.cookie, path);
+                continue;
+            }
+            if event.mask.contains(EventMask::MOVED_TO) {
+                if let Some(from) = moved_from.remove(&event.cookie) {
+                    for watched_dir in watched.values_mut() {
+                        if let Some(rest) = watched_dir.strip_prefix(from.as_str()) {
+                            *watched_dir = format!("{}{}", path, rest);
+                        }
+                    }
+                    touched.renames.push((from, path.clone()));
+                }
+            }
+
+            touched.subtrees.insert(path);
+        }
+
+        // Entries moved out of the directory are gone.
+        touched.subtrees.extend(moved_from.into_values());
+        touched
+    }
+}
+
+impl FS0X {
+    /// Publish a local directory every time it changes
+    ///
+    /// The directory is published in full first, then only the entries
+    /// touched by the changes are read again and compared with the tree
+    /// kept in memory. Changes are published once the directory saw none for
+    /// `debounce`. The whole directory is read again when events were lost
+    /// or a publication failed. Only returns when the directory cannot be
+    /// watched anymore.
+    pub fn watch(&mut self, dir: &Path, debounce: Duration) -> Result<(), LocalError> {
+        let mut inotify = Inotify::init()?;
+        let mut buffer = [0; EVENTS_BUFFER_SIZE];
+        let mut watched = HashMap::new();
+        let mut touched = Touched { overflow: true, ..Default::default() };
+
+        loop {
+            // Directories are watched before being read, so that no change
+            // made while publishing is missed.
+            let result = match touched.overflow {
+                true => watch_dirs(&mut inotify, &mut watched, dir, "/")
+                    .map_err(LocalError::from)
+                    .and_then(|_| self.push(dir, false)),
+                false => touched.subtrees.iter()
+                    .map(|path| (local_path(dir, path), path))
+                    .filter(|(local, _)| local.is_dir())
+                    .try_for_each(|(local, path)| watch_dirs(&mut inotify, &mut watched, &local, path))
+                    .map_err(LocalError::from)
+                    .and_then(|_| self.push_changes(dir, touched)),
+            };
+            let failed = match result {
+                Ok(diff) => {
+                    print!("{}", diff);
+                    false
+                },
+                Err(e) => {
+                    eprintln!("failed to publish {}: {}", dir.display(), e);
+                    true
+                },
+            };
+
+            let mut events = Vec::new();
+            read_events(&mut inotify, &mut buffer, &mut events, true)?;
+            loop {
+                std::thread::sleep(debounce);
+                if !read_events(&mut inotify, &mut buffer, &mut events, false)? {
+                    break;
+                }
+            }
+
+            if !dir.is_dir() {
+                return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} was removed", dir.display())).into());
+            }
+
+            touched = Touched::from_events(&events, &mut watched);
+            touched.overflow |= failed;
+            unwatch_missing(&mut inotify, &mut watched, dir, &touched.subtrees);
+        }
+    }
+
+    /// Publish the changes made to the entries of a local directory touched
+    /// since the last publication
+    ///
+    /// Moves are published as they are, the touched entries are read again
+    /// and compared with the tree kept from the last publication.
+    fn push_changes(&mut self, dir: &Path, touched: Touched) -> Result<Diff, LocalError> {
+        let Touched { renames, mut subtrees, dirs, .. } = touched;
+        let mut diff = Diff::default();
+        let mut ops = Vec::new();
+
+        for (from, to) in renames {
+            let before = self.fs_tree.get_entry(&from).cloned();
+            let op = Operation::Rename { from: from.clone(), to: to.clone(), exchange: false };
+
+            match (before, op.apply(&mut self.fs_tree)) {
+                (Some(before), Ok(())) => {
+                    let after = self.fs_tree.get_entry(&to).cloned().unwrap_or_else(|| before.clone());
+                    diff.changes.push(Change::Moved { before: Box::new(before), after: Box::new(after) });
+                    ops.push(op);
+                },
+                // Moved over another entry or never published, both ends
+                // are compared instead.
+                _ => {
+                    subtrees.insert(from);
+                },
+            }
+        }
+
+        // Entries below a touched subtree are read along with it, ancestors
+        // are sorted before their content.
+        let mut roots: Vec<&String> = Vec::new();
+        for path in &subtrees {
+            if !roots.last().is_some_and(|root| path.starts_with(root.as_str())) {
+                roots.push(path);
+            }
+        }
+
+        let mut before = FSTree::new("/".to_string());
+        let mut after = FSTree::new("/".to_string());
+        {
+            let mut content = changed_content(self.cipher.as_ref(), &self.fs_tree);
+            for path in &roots {
+                for entry in self.fs_tree.get_subtree(path) {
+                    before.add_entry(entry.clone());
+                }
+                read_local_entry(&mut after, &local_path(dir, path), path, true, &mut content)?;
+            }
+            for path in dirs.iter().filter(|path| !roots.iter().any(|root| path.starts_with(root.as_str()))) {
+                if let Some(entry) = self.fs_tree.get_entry(path) {
+                    before.add_entry(entry.clone());
+                }
+                read_local_entry(&mut after, &local_path(dir, path), path, false, &mut content)?;
+            }
+        }
+
+        let changes = Diff::between(&before, &after);
+        let mut changed = changes.operations();
+        self.upload_contents(dir, &mut changed)?;
+        self.apply_operations(&changed);
+        ops.extend(changed);
+        diff.changes.extend(changes.changes);
+
+        if !ops.is_empty() {
+            self.publish_operations(ops)
+                .and_then(|_| self.wait_for_confirmation())
+                .map_err(LocalError::Publish)?;
+        }
+
+        Ok(diff)
+    }
+}
+
+/// Watch a local directory mirrored at `path` and the directories below it
+///
+/// Directories already watched are left as is.
+fn watch_dirs(inotify: &mut Inotify, watched: &mut HashMap<WatchDescriptor, String>, dir: &Path, path: &str) -> io::Result<()> {
+    let wd = inotify.watches().add(dir, watch_mask())?;
+    watched.insert(wd, path.to_string());
+
+    for dir_entry in fs::read_dir(dir)? {
+        let dir_entry = dir_entry?;
+        if dir_entry.file_type()?.is_dir() {
+            let name = dir_entry.file_name().to_string_lossy().into_owned();
+            watch_dirs(inotify, watched, &dir_entry.path(), &format!("{}{}/", path, name))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Stop watching the directories below the touched subtrees that are gone,
+/// as the ones moved out of the watched directory
+fn unwatch_missing(inotify: &mut Inotify, watched: &mut HashMap<WatchDescriptor, String>, dir: &Path, subtrees: &BTreeSet<String>) {
+    let missing: Vec<WatchDescriptor> = watched.iter()
+        .filter(|(_, path)| subtrees.iter().any(|subtree| path.starts_with(subtree.as_str())))
+        .filter(|(_, path)| !local_path(dir, path).is_dir())
+        .map(|(wd, _)| wd.clone())
+        .collect();
+
+    for wd in missing {
+        watched.remove(&wd);
+        let _ = inotify.watches().remove(wd);
+    }
+}
+
+/// Read the queued events, waiting for one when `block` is set, returns
+/// whether there was any
+fn read_events(inotify: &mut Inotify, buffer: &mut [u8], events: &mut Vec<WatchEvent>, block: bool) -> io::Result<bool> {
+    let mut read = false;
+
+    loop {
+        let result = match block && !read {
+            true => inotify.read_events_blocking(buffer),
+            false => inotify.read_events(buffer),
+        };
+
+        match result {
+            Ok(batch) => {
+                let count = events.len();
+                events.extend(batch.map(|event| WatchEvent {
+                    wd: event.wd,
+                    mask: event.mask,
+                    cookie: event.cookie,
+                    name: event.name.map(|name| name.to_string_lossy().into_owned()),
+                }));
+                if events.len() == count {
+                    return Ok(read);
+                }
+                read = true;
+            },
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(read),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_notices_changes_below_a_watched_directory() {
+        let dir = std::env::temp_dir().join(format!("fs0x-watch-{}", std::process::id()));
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        fs::write(dir.join("old.txt"), b"old").unwrap();
+
+        let mut inotify = Inotify::init().unwrap();
+        let mut buffer = [0; EVENTS_BUFFER_SIZE];
+        let mut watched = HashMap::new();
+        let mut events = Vec::new();
+        watch_dirs(&mut inotify, &mut watched, &dir, "/").unwrap();
+        let idle = read_events(&mut inotify, &mut buffer, &mut events, false).unwrap();
+
+        fs::write(dir.join("docs/a.txt"), b"hello").unwrap();
+        fs::rename(dir.join("docs"), dir.join("notes")).unwrap();
+        fs::write(dir.join("notes/b.txt"), b"hello").unwrap();
+        fs::remove_file(dir.join("old.txt")).unwrap();
+        let changed = read_events(&mut inotify, &mut buffer, &mut events, false).unwrap();
+        let drained = read_events(&mut inotify, &mut buffer, &mut events, false).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!idle);
+        assert!(changed);
+        assert!(!drained);
+
+        let touched = Touched::from_events(&events, &mut watched);
+        assert_eq!(touched.renames, vec![("/docs/".to_string(), "/notes/".to_string())]);
+        assert_eq!(touched.subtrees, BTreeSet::from([
+            "/docs/a.txt/".to_string(),
+            "/notes/".to_string(),
+            "/notes/b.txt/".to_string(),
+            "/old.txt/".to_string(),
+        ]));
+        assert_eq!(touched.dirs, BTreeSet::from(["/docs/".to_string(), "/notes/".to_string()]));
+        assert!(!touched.overflow);
+    }
+}
@@ -0,0 +1,99 @@
+use libc::c_int;
+use std::collections::BTreeMap;
+
+use super::inode::InodeAttributes;
+
+/// Convert inode extended attributes to their `mirroring::Entry` form
+pub fn encode_xattrs(xattrs: &BTreeMap<Vec<u8>, Vec<u8>>) -> BTreeMap<String, String> {
+    xattrs.iter()
+        .map(|(name, value)| (String::from_utf8_lossy(name).to_string(), hex::encode(value)))
+        .collect()
+}
+
+/// Convert extended attributes of a `mirroring::Entry` to their inode form
+///
+/// Values that are not valid hex are skipped.
+pub fn decode_xattrs(xattrs: &BTreeMap<String, String>) -> BTreeMap<Vec<u8>, Vec<u8>> {
+    xattrs.iter()
+        .filter_map(|(name, value)| Some((name.as_bytes().to_vec(), hex::decode(value).ok()?)))
+        .collect()
+}
+
+/// Check that a user may read or write an extended attribute of an inode
+///
+/// `user.` attributes follow the inode permissions, other namespaces are
+/// reserved to root except for reading `security.` and `system.` ones.
+pub fn check_xattr_access(attr: &InodeAttributes, uid: u32, gid: u32, name: &[u8], mask: i32) -> Result<(), c_int> {
+    let allowed = match name.starts_with(b"user.") {
+        true => attr.check_access(uid, gid, mask),
+        false if uid == 0 => true,
+        false => mask == libc::R_OK && (name.starts_with(b"security.") || name.starts_with(b"system.")),
+    };
+
+    match allowed {
+        true => Ok(()),
+        false if name.starts_with(b"user.") => Err(libc::EACCES),
+        false => Err(libc::EPERM),
+    }
+}
+
+/// Build the NUL separated list of attribute names returned by `listxattr`
+pub fn list_xattrs(xattrs: &BTreeMap<Vec<u8>, Vec<u8>>) -> Vec<u8> {
+    let mut list = Vec::new();
+
+    for name in xattrs.keys() {
+        list.extend_from_slice(name);
+        list.push(0);
+    }
+
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::inode::FileKind;
+    use std::time::SystemTime;
+
+    #[test]
+    fn it_round_trips_xattrs_through_entries() {
+        let xattrs = BTreeMap::from([
+            (b"user.tag".to_vec(), b"blue".to_vec()),
+            (b"security.selinux".to_vec(), vec![0, 255]),
+        ]);
+
+        let encoded = encode_xattrs(&xattrs);
+
+        assert_eq!(encoded["security.selinux"], "00ff");
+        assert_eq!(decode_xattrs(&encoded), xattrs);
+        assert_eq!(list_xattrs(&xattrs), b"security.selinux\0user.tag\0");
+    }
+
+    #[test]
+    fn it_restricts_namespaces_to_root() {
+        let attr = InodeAttributes {
+            inode: 2,
+            pinode: Some(1),
+            fname: "file.txt".to_string(),
+            open_file_handles: 0,
+            size: 0,
+            last_accessed: SystemTime::now(),
+            last_modified: SystemTime::now(),
+            last_metadata_changed: SystemTime::now(),
+            kind: FileKind::File,
+            mode: 0o644,
+            hardlinks: 1,
+            uid: 1000,
+            gid: 1000,
+            xattrs: Default::default(),
+            links: vec![],
+        };
+
+        assert_eq!(check_xattr_access(&attr, 1000, 1000, b"user.tag", libc::W_OK), Ok(()));
+        assert_eq!(check_xattr_access(&attr, 1001, 1001, b"user.tag", libc::W_OK), Err(libc::EACCES));
+        assert_eq!(check_xattr_access(&attr, 1000, 1000, b"security.selinux", libc::R_OK), Ok(()));
+        assert_eq!(check_xattr_access(&attr, 1000, 1000, b"security.selinux", libc::W_OK), Err(libc::EPERM));
+        assert_eq!(check_xattr_access(&attr, 1000, 1000, b"trusted.tag", libc::R_OK), Err(libc::EPERM));
+        assert_eq!(check_xattr_access(&attr, 0, 0, b"trusted.tag", libc::W_OK), Ok(()));
+    }
+}
@@ -0,0 +1,201 @@
+use aes::cipher::{KeyIvInit, StreamCipher};
+use ethers::core::rand::{thread_rng, RngCore};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use thiserror::Error;
+
+type Aes256Ctr = ctr::Ctr128BE<aes::Aes256>;
+type HmacSha256 = Hmac<Sha256>;
+
+/// Version of the encrypted format, first byte of every sealed blob.
+const VERSION: u8 = 1;
+
+const IV_LENGTH: usize = 16;
+const TAG_LENGTH: usize = 32;
+
+/// Prefix of the post contents holding an encrypted text.
+const TEXT_PREFIX: &str = "fs0x-enc1:";
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CipherError {
+    #[error("encrypted data is truncated")]
+    Truncated,
+
+    #[error("unsupported encryption version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("encrypted data failed authentication")]
+    Authentication,
+
+    #[error("encrypted text is not valid hex: {0}")]
+    Encoding(#[from] hex::FromHexError),
+
+    #[error("decrypted text is not valid UTF-8")]
+    Utf8,
+
+    #[error("content is encrypted and no key was provided")]
+    MissingKey,
+}
+
+/// Authenticated encryption of the data fs0x publishes.
+///
+/// Data is encrypted with AES-256 in CTR mode under a random IV, then
+/// authenticated with HMAC-SHA256 over the version, IV and ciphertext. The
+/// keys derive from a single secret.
+#[derive(Clone)]
+pub struct Cipher {
+    encryption_key: [u8; 32],
+    authentication_key: [u8; 32],
+    convergence_key: [u8; 32],
+}
+
+impl Cipher {
+    pub fn new(secret: [u8; 32]) -> Self {
+        Cipher {
+            encryption_key: derive_key(&secret, "fs0x encryption key"),
+            authentication_key: derive_key(&secret, "fs0x authentication key"),
+            convergence_key: derive_key(&secret, "fs0x convergence key"),
+        }
+    }
+
+    /// Encrypts and authenticates data.
+    pub fn seal(&self, plaintext: &[u8]) -> Vec<u8> {
+        let mut iv = [0u8; IV_LENGTH];
+        thread_rng().fill_bytes(&mut iv);
+
+        self.seal_with_iv(iv, plaintext)
+    }
+
+    /// Encrypts and authenticates data, the same data always giving the same
+    /// result.
+    ///
+    /// The IV derives from the data, so that identical contents are stored
+    /// once. It reveals which sealed blobs hold the same data.
+    pub fn seal_convergent(&self, plaintext: &[u8]) -> Vec<u8> {
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(&self.convergence_key).expect("HMAC accepts any key length");
+        mac.update(plaintext);
+
+        let mut iv = [0u8; IV_LENGTH];
+        iv.copy_from_slice(&mac.finalize().into_bytes()[..IV_LENGTH]);
+
+        self.seal_with_iv(iv, plaintext)
+    }
+
+    fn seal_with_iv(&self, iv: [u8; IV_LENGTH], plaintext: &[u8]) -> Vec<u8> {
+        let mut sealed = Vec::with_capacity(1 + IV_LENGTH + plaintext.len() + TAG_LENGTH);
+        sealed.push(VERSION);
+        sealed.extend_from_slice(&iv);
+        sealed.extend_from_slice(plaintext);
+
+        Aes256Ctr::new(&self.encryption_key.into(), &iv.into()).apply_keystream(&mut sealed[1 + IV_LENGTH..]);
+
+        let tag = self.mac(&sealed).finalize().into_bytes();
+        sealed.extend_from_slice(&tag);
+
+        sealed
+    }
+
+    /// Authenticates and decrypts data sealed with the same secret.
+    pub fn open(&self, sealed: &[u8]) -> Result<Vec<u8>, CipherError> {
+        if sealed.len() < 1 + IV_LENGTH + TAG_LENGTH {
+            return Err(CipherError::Truncated);
+        }
+        if sealed[0] != VERSION {
+            return Err(CipherError::UnsupportedVersion(sealed[0]));
+        }
+
+        let (data, tag) = sealed.split_at(sealed.len() - TAG_LENGTH);
+        self.mac(data).verify_slice(tag).map_err(|_| CipherError::Authentication)?;
+
+        let (iv, ciphertext) = data[1..].split_at(IV_LENGTH);
+        let mut plaintext = ciphertext.to_vec();
+        Aes256Ctr::new(&self.encryption_key.into(), iv.into()).apply_keystream(&mut plaintext);
+
+        Ok(plaintext)
+    }
+
+    /// Encrypts a text into a post content.
+    pub fn seal_text(&self, text: &str) -> String {
+        format!("{}{}", TEXT_PREFIX, hex::encode(self.seal(text.as_bytes())))
+    }
+
+    fn mac(&self, data: &[u8]) -> HmacSha256 {
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(&self.authentication_key).expect("HMAC accepts any key length");
+        mac.update(data);
+        mac
+    }
+}
+
+/// Reads a post content, decrypting it when it was sealed.
+///
+/// Plain contents are returned as is.
+pub fn open_text(cipher: Option<&Cipher>, content: &str) -> Result<String, CipherError> {
+    let sealed = match content.strip_prefix(TEXT_PREFIX) {
+        Some(sealed) => hex::decode(sealed)?,
+        None => {
+            return Ok(content.to_string());
+        }
+    };
+
+    let plaintext = cipher.ok_or(CipherError::MissingKey)?.open(&sealed)?;
+
+    String::from_utf8(plaintext).map_err(|_| CipherError::Utf8)
+}
+
+/// Derives a key bound to a context from a secret.
+pub fn derive_key(secret: &[u8], context: &str) -> [u8; 32] {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(context.as_bytes());
+
+    mac.finalize().into_bytes().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_seals_and_opens_data() {
+        let cipher = Cipher::new([7; 32]);
+
+        let sealed = cipher.seal(b"secret content");
+
+        assert_ne!(&sealed[1 + IV_LENGTH..1 + IV_LENGTH + 14], b"secret content");
+        assert_eq!(cipher.open(&sealed).unwrap(), b"secret content");
+        assert_ne!(cipher.seal(b"secret content"), sealed);
+    }
+
+    #[test]
+    fn it_seals_the_same_data_the_same_way_when_convergent() {
+        let cipher = Cipher::new([7; 32]);
+
+        let sealed = cipher.seal_convergent(b"secret content");
+
+        assert_eq!(cipher.seal_convergent(b"secret content"), sealed);
+        assert_ne!(Cipher::new([8; 32]).seal_convergent(b"secret content"), sealed);
+        assert_eq!(cipher.open(&sealed).unwrap(), b"secret content");
+    }
+
+    #[test]
+    fn it_rejects_tampered_or_foreign_data() {
+        let cipher = Cipher::new([7; 32]);
+        let mut sealed = cipher.seal(b"secret content");
+
+        assert_eq!(Cipher::new([8; 32]).open(&sealed), Err(CipherError::Authentication));
+        assert_eq!(cipher.open(&sealed[..20]), Err(CipherError::Truncated));
+
+        sealed[20] ^= 1;
+        assert_eq!(cipher.open(&sealed), Err(CipherError::Authentication));
+    }
+
+    #[test]
+    fn it_opens_plain_and_sealed_texts() {
+        let cipher = Cipher::new([7; 32]);
+        let sealed = cipher.seal_text("{\"root\":\"/\"}");
+
+        assert!(!sealed.contains("root"));
+        assert_eq!(open_text(Some(&cipher), &sealed).unwrap(), "{\"root\":\"/\"}");
+        assert_eq!(open_text(None, &sealed), Err(CipherError::MissingKey));
+        assert_eq!(open_text(None, "{}").unwrap(), "{}");
+    }
+}
@@ -1,4 +1,6 @@
-use reqwest::{Client, ClientBuilder, Response, StatusCode};
+use std::time::Duration;
+
+use reqwest::{multipart::Form, Client, ClientBuilder, Response, StatusCode};
 use serde::Serialize;
 use thiserror::Error;
 use url::{ParseError, Url};
@@ -20,6 +22,17 @@ pub enum HttpClientError {
     ResponseError { status: StatusCode, data: String },
 }
 
+/// How long idle connections are kept in the pool for reuse.
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Interval of the TCP keepalive probes sent on pooled connections.
+const TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+
+/// Client for the Aleph API.
+///
+/// Clones share the same connection pool, build one client and clone it
+/// rather than building a new one per request.
+#[derive(Clone)]
 pub struct HttpClient {
     pub api_url: String,
     pub requester: Client,
@@ -27,7 +40,9 @@ pub struct HttpClient {
 
 impl HttpClient {
     pub fn new() -> Result<Self, HttpClientError> {
-        let builder = ClientBuilder::new();
+        let builder = ClientBuilder::new()
+            .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+            .tcp_keepalive(TCP_KEEPALIVE);
         let requester = builder.build()?;
 
         Ok(HttpClient {
@@ -36,6 +51,7 @@ impl HttpClient {
         })
     }
 
+    #[allow(dead_code)]
     pub fn with_api_url(mut self, api_url: &str) -> Result<Self, HttpClientError> {
         Url::parse(api_url)?;
 
@@ -48,6 +64,7 @@ impl HttpClient {
         }
     }
 
+    #[allow(dead_code)]
     pub fn with_requester(mut self, requester: Client) -> Self {
         self.requester = requester;
         self
@@ -64,35 +81,44 @@ impl HttpClient {
 
         let res = self.requester.execute(http_req).await?;
 
-        match res.status().as_u16() < 200 || res.status().as_u16() >= 300 {
-            true => {
-                let status = res.status();
-                let data = res.text().await?;
-
-                Err(HttpClientError::ResponseError { status, data })
-            }
-            false => Ok(res),
-        }
+        check_status(res).await
     }
 
     pub async fn do_post_request<T: Serialize>(&self, req: Request, body: T) -> Result<Response, HttpClientError> {
         let url = req.get_url(self.api_url.as_str())?;
 
-        let res = reqwest::Client::new()
+        let res = self.requester
             .post(url)
             .json(&body)
             .send()
             .await?;
 
-        match res.status().as_u16() < 200 || res.status().as_u16() >= 300 {
-            true => {
-                let status = res.status();
-                let data = res.text().await?;
+        check_status(res).await
+    }
 
-                Err(HttpClientError::ResponseError { status, data })
-            }
-            false => Ok(res),
+    pub async fn do_multipart_request(&self, req: Request, form: Form) -> Result<Response, HttpClientError> {
+        let url = req.get_url(self.api_url.as_str())?;
+
+        let res = self.requester
+            .post(url)
+            .multipart(form)
+            .send()
+            .await?;
+
+        check_status(res).await
+    }
+}
+
+/// Turns a non 2xx response into an `HttpClientError::ResponseError`.
+async fn check_status(res: Response) -> Result<Response, HttpClientError> {
+    match res.status().as_u16() < 200 || res.status().as_u16() >= 300 {
+        true => {
+            let status = res.status();
+            let data = res.text().await?;
+
+            Err(HttpClientError::ResponseError { status, data })
         }
+        false => Ok(res),
     }
 }
 
@@ -104,7 +130,7 @@ mod tests {
     fn it_builds_the_http_client() {
         let client = HttpClient::new();
 
-        assert_eq!(true, client.is_ok());
+        assert!(client.is_ok());
     }
 
     #[test]
@@ -113,14 +139,14 @@ mod tests {
             .unwrap()
             .with_api_url("https://api2.aleph.im");
 
-        assert_eq!(true, client.is_ok());
+        assert!(client.is_ok());
     }
 
     #[test]
     fn it_fails_to_build_the_http_client_with_invalid_api_url() {
         let client = HttpClient::new().unwrap().with_api_url("");
 
-        assert_eq!(true, client.is_err());
+        assert!(client.is_err());
     }
 
     #[test]
@@ -129,6 +155,6 @@ mod tests {
             .unwrap()
             .with_api_url("https://api2.aleph.im/");
 
-        assert_eq!(true, client.is_err());
+        assert!(client.is_err());
     }
 }
\ No newline at end of file
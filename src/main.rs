@@ -2,14 +2,22 @@ mod http;
 mod sdk;
 mod cli;
 mod core;
+mod crypto;
 mod mirroring;
 
 use clap::Parser;
+use sdk::common::MessageSigner;
 use fuser::MountOption;
-use sdk::AlephSDK;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 fn main() {
     let args = cli::Args::parse();
+
+    if let Some(command) = args.command {
+        return run_command(command);
+    }
+
     let mut options = vec![
         MountOption::RW,
         MountOption::AutoUnmount,
@@ -20,10 +28,183 @@ fn main() {
         options.push(MountOption::AllowRoot);
     }
 
+    let mut fs0x = open_fs0x(args.identity);
+    if let Some(quota) = args.quota {
+        fs0x = fs0x.with_quota(quota);
+    }
+    if let Some(cache) = open_cache(args.cache_dir, args.cache_size) {
+        fs0x = fs0x.with_cache(cache);
+    }
+    if let Some(spool) = open_spool(args.spool_dir) {
+        fs0x = fs0x.with_spool(spool);
+    }
+    let worker = fs0x.sync_worker(Duration::from_secs(args.sync_interval));
+
+    let mut session = fuser::Session::new(fs0x, Path::new(&args.mount_point.unwrap_or_default()), &options).unwrap();
+    worker.spawn(session.notifier());
+    session.run().unwrap();
+}
+
+fn run_command(command: cli::Command) {
+    let client = http::HttpClient::new().unwrap();
+    let sdk = sdk::AlephSDK::new(client);
+    let rt = tokio::runtime::Runtime::new().unwrap();
+
+    match command {
+        cli::Command::Status { identity } => {
+            let source = tree_source(identity);
+
+            match core::Status::fetch(&rt, &sdk, &source) {
+                Ok(Some(status)) => print!("{}", status),
+                Ok(None) => {
+                    eprintln!("no tree found on {}", source.channel);
+                    std::process::exit(1);
+                },
+                Err(e) => {
+                    eprintln!("failed to fetch the tree of {}: {}", source.channel, e);
+                    std::process::exit(1);
+                }
+            }
+        },
+        cli::Command::Restore { identity, point, dry_run } => {
+            match open_fs0x(identity).restore(&point, dry_run) {
+                Ok(diff) if diff.is_empty() => println!("nothing to restore, the tree is unchanged"),
+                Ok(diff) => print!("{}", diff),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        },
+        cli::Command::Diff { identity, from, to, local } => {
+            let source = tree_source(identity);
+            let from = fetch_tree(&rt, &sdk, &source, Some(&from));
+            let to = match local {
+                Some(dir) => core::read_local_tree(Path::new(&dir), source.cipher.as_ref()).unwrap_or_else(|e| {
+                    eprintln!("failed to read {}: {}", dir, e);
+                    std::process::exit(1);
+                }),
+                None => fetch_tree(&rt, &sdk, &source, to.as_deref()),
+            };
+
+            print!("{}", mirroring::Diff::between(&from, &to));
+        },
+        cli::Command::Push { identity, dir, dry_run } => {
+            match open_fs0x(identity).push(Path::new(&dir), dry_run) {
+                Ok(diff) if diff.is_empty() => println!("nothing to push, the tree is up to date"),
+                Ok(diff) => print!("{}", diff),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        },
+        cli::Command::Pull { identity, dir, delete, dry_run } => {
+            match open_fs0x(identity).pull(Path::new(&dir), delete, dry_run) {
+                Ok(diff) if diff.is_empty() => println!("nothing to pull, {} is up to date", dir),
+                Ok(diff) => print!("{}", diff),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        },
+        cli::Command::Watch { identity, dir, debounce } => {
+            if let Err(e) = open_fs0x(identity).watch(Path::new(&dir), Duration::from_millis(debounce)) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+    }
+}
+
+/// Fetch a version of the tree a command reads
+fn fetch_tree(rt: &tokio::runtime::Runtime, sdk: &sdk::AlephSDK, source: &core::TreeSource, point: Option<&str>) -> mirroring::FSTree {
+    match core::fetch_tree(rt, sdk, source, point) {
+        Ok(fs_tree) => fs_tree,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Build the filesystem of an identity, with no local storage
+fn open_fs0x(identity: cli::Identity) -> core::FS0X {
     let client = http::HttpClient::new().unwrap();
     let sdk = sdk::AlephSDK::new(client);
 
-    let fs0x = core::FS0X::new(sdk, args.id);
+    let signer = load_signer(&identity);
+    let cipher = load_cipher(&identity, &signer);
+    let id = identity.id.unwrap_or_default();
 
-    fuser::mount2(fs0x, args.mount_point, &options).unwrap();
-}
\ No newline at end of file
+    let fs0x = core::FS0X::new(sdk, signer, id)
+        .with_allowed_senders(identity.allowed_senders);
+    match cipher {
+        Some(cipher) => fs0x.with_cipher(cipher),
+        None => fs0x,
+    }
+}
+
+/// Open the local content cache, reads go to the network without it
+fn open_cache(dir: Option<String>, size: u64) -> Option<core::ContentCache> {
+    if size == 0 {
+        return None;
+    }
+
+    let dir = match dir {
+        Some(dir) => PathBuf::from(dir),
+        None => dirs::cache_dir()?.join("fs0x"),
+    };
+
+    core::ContentCache::open(&dir, size)
+        .map_err(|e| eprintln!("failed to open the content cache in {}: {}", dir.display(), e))
+        .ok()
+}
+
+/// Open the spool of the contents not uploaded yet, they are only kept in
+/// memory without it
+fn open_spool(dir: Option<String>) -> Option<core::Spool> {
+    let dir = match dir {
+        Some(dir) => PathBuf::from(dir),
+        None => dirs::data_local_dir()?.join("fs0x").join("spool"),
+    };
+
+    core::Spool::open(&dir)
+        .map_err(|e| eprintln!("failed to open the spool in {}: {}", dir.display(), e))
+        .ok()
+}
+
+/// Build the source of the tree a command reads
+fn tree_source(identity: cli::Identity) -> core::TreeSource {
+    let signer = load_signer(&identity);
+    let address = signer.get_address();
+
+    core::TreeSource {
+        channel: core::channel_name(&address, identity.id.as_deref().unwrap_or_default()),
+        cipher: load_cipher(&identity, &signer),
+        senders: std::iter::once(address).chain(identity.allowed_senders).collect(),
+    }
+}
+
+/// Derive the encryption key of the tree from the signing key, if enabled
+fn load_cipher(identity: &cli::Identity, signer: &sdk::common::DefaultEthereumSigner) -> Option<crypto::Cipher> {
+    if !identity.encrypt {
+        return None;
+    }
+
+    let private_key = hex::decode(signer.private_key().trim_start_matches("0x")).unwrap();
+    let context = format!("fs0x {}", identity.id.as_deref().unwrap_or_default());
+
+    Some(crypto::Cipher::new(crypto::derive_key(&private_key, &context)))
+}
+
+fn load_signer(identity: &cli::Identity) -> sdk::common::DefaultEthereumSigner {
+    match cli::load_signer(identity) {
+        Ok(signer) => signer,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use std::fmt;
+use std::time::SystemTime;
+
+use super::{Entry, FSTree, Operation};
+
+/// A difference between two versions of an entry.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Change {
+    /// The entry only exists in the new tree.
+    Added(Entry),
+    /// The entry only exists in the old tree.
+    Removed(Entry),
+    /// The entry exists in both trees, with other content or attributes.
+    Modified { before: Box<Entry>, after: Box<Entry> },
+    /// The file was moved to another path, keeping its content.
+    Moved { before: Box<Entry>, after: Box<Entry> },
+    /// Only the permission or the owner of the entry changed.
+    PermissionChanged { before: Box<Entry>, after: Box<Entry> },
+}
+
+impl Change {
+    /// Returns the path of the changed entry, in the new tree when it exists.
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Added(entry) | Change::Removed(entry) => entry.path(),
+            Change::Modified { after, .. } | Change::Moved { after, .. } | Change::PermissionChanged { after, .. } => after.path(),
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = |entry: &Entry| entry.path().trim_end_matches('/').to_string();
+
+        match self {
+            Change::Added(entry) => write!(f, "+ {}", path(entry)),
+            Change::Removed(entry) => write!(f, "- {}", path(entry)),
+            Change::Modified { after, .. } => write!(f, "~ {}", path(after)),
+            Change::Moved { before, after } => write!(f, "> {} -> {}", path(before), path(after)),
+            Change::PermissionChanged { before, after } => {
+                let (uid, gid) = after.owner();
+                write!(f, "* {} {} -> {} {}:{}", path(after), before.permission(), after.permission(), uid, gid)
+            },
+        }
+    }
+}
+
+/// The changes turning a `FSTree` into another, ordered by path.
+#[derive(Debug, Default, PartialEq)]
+pub struct Diff {
+    pub changes: Vec<Change>,
+}
+
+impl Diff {
+    /// Compares two trees.
+    ///
+    /// Entries are matched by path, then the files removed from the old tree
+    /// are matched by content hash with the ones added to the new tree.
+    /// Creation times and file type bits of permissions are not compared, a
+    /// local copy of a tree cannot keep them.
+    pub fn between(from: &FSTree, to: &FSTree) -> Self {
+        let mut changes: Vec<Change> = to.get_entries().iter()
+            .filter_map(|after| match from.get_entry(after.path()) {
+                None => Some(Change::Added(after.clone())),
+                Some(before) if normalized(before) == normalized(after) => None,
+                Some(before) if only_permission_changed(before, after) => Some(Change::PermissionChanged {
+                    before: Box::new(before.clone()),
+                    after: Box::new(after.clone()),
+                }),
+                Some(before) => Some(Change::Modified {
+                    before: Box::new(before.clone()),
+                    after: Box::new(after.clone()),
+                }),
+            })
+            .collect();
+
+        for before in from.get_entries().iter().filter(|before| to.get_entry(before.path()).is_none()) {
+            let moved = changes.iter().position(|change| match change {
+                Change::Added(after) => same_content(before, after),
+                _ => false,
+            });
+
+            match moved {
+                Some(position) => {
+                    let after = match changes.remove(position) {
+                        Change::Added(after) => after,
+                        _ => unreachable!(),
+                    };
+                    changes.push(Change::Moved {
+                        before: Box::new(before.clone()),
+                        after: Box::new(after),
+                    });
+                },
+                None => changes.push(Change::Removed(before.clone())),
+            }
+        }
+        changes.sort_by(|a, b| a.path().cmp(b.path()));
+
+        Diff { changes }
+    }
+
+    /// Whether both trees hold the same entries.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Returns the operations turning the old tree into the new one.
+    ///
+    /// Entries are created parents first, then files are moved out of the
+    /// directories about to be removed, and entries are removed last.
+    pub fn operations(&self) -> Vec<Operation> {
+        let mut replaced = Vec::new();
+        let mut creations = Vec::new();
+        let mut moves = Vec::new();
+        let mut updates = Vec::new();
+        let mut removals = Vec::new();
+
+        for change in &self.changes {
+            match change {
+                Change::Added(entry) => creations.push(create(entry)),
+                Change::Removed(entry) => removals.push(Operation::Delete { path: entry.path().to_string() }),
+                // An entry changing type is replaced as a whole, a directory
+                // once the entries below it are moved or removed.
+                Change::Modified { before, after } if before.entry_type() != after.entry_type() => {
+                    if !matches!(**before, Entry::Directory(_)) {
+                        replaced.push(Operation::Delete { path: before.path().to_string() });
+                    }
+                    creations.push(create(after));
+                },
+                Change::Modified { after, .. } | Change::PermissionChanged { after, .. } => {
+                    updates.push(Operation::Setattr { entry: *after.clone() });
+                },
+                Change::Moved { before, after } => {
+                    moves.push(Operation::Rename {
+                        from: before.path().to_string(),
+                        to: after.path().to_string(),
+                        exchange: false,
+                    });
+
+                    let mut renamed = *before.clone();
+                    renamed.set_path(after.path().to_string());
+                    renamed.set_name(after.name().to_string());
+                    if renamed != **after {
+                        moves.push(Operation::Setattr { entry: *after.clone() });
+                    }
+                },
+            }
+        }
+
+        [replaced, creations, moves, updates, removals].concat()
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether two versions of an entry only differ by their permission or owner.
+fn only_permission_changed(before: &Entry, after: &Entry) -> bool {
+    let mut before = before.clone();
+    let (uid, gid) = after.owner();
+    before.set_permission(after.permission().to_string());
+    before.set_owner(uid, gid);
+
+    normalized(&before) == normalized(after)
+}
+
+/// Returns the entry without the attributes left out of comparisons.
+fn normalized(entry: &Entry) -> Entry {
+    let mut entry = entry.clone();
+    entry.set_created_at(SystemTime::UNIX_EPOCH);
+    if let Ok(mode) = u32::from_str_radix(entry.permission(), 8) {
+        entry.set_permission(format!("{:o}", mode & 0o7777));
+    }
+
+    entry
+}
+
+/// Whether two files hold the same uploaded content.
+fn same_content(before: &Entry, after: &Entry) -> bool {
+    match (before, after) {
+        (Entry::File(before), Entry::File(after)) => {
+            let content = before.content();
+            !content.is_empty() && content == after.content()
+        },
+        _ => false,
+    }
+}
+
+/// Returns the operation creating an entry.
+fn create(entry: &Entry) -> Operation {
+    match entry {
+        Entry::Directory(_) => Operation::Mkdir { entry: entry.clone() },
+        _ => Operation::Create { entry: entry.clone() },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirroring::{Directory, File};
+
+    fn tree(entries: Vec<Entry>) -> FSTree {
+        let mut fs_tree = FSTree::new("/".to_string());
+        for entry in entries {
+            fs_tree.add_entry(entry);
+        }
+        fs_tree
+    }
+
+    fn dir(path: &str) -> Entry {
+        let name = path.trim_end_matches('/').rsplit('/').next().unwrap();
+
+        Entry::Directory(Directory::new(name.to_string(), path.to_string(), "755".to_string(), SystemTime::UNIX_EPOCH, 0, 0))
+    }
+
+    fn file(path: &str, size: u64) -> Entry {
+        let name = path.trim_end_matches('/').rsplit('/').next().unwrap();
+
+        Entry::File(File::new(name.to_string(), path.to_string(), size, "644".to_string(), SystemTime::UNIX_EPOCH, 0, 0))
+    }
+
+    fn stored(path: &str, hash: &str) -> Entry {
+        let mut entry = file(path, 1);
+        if let Entry::File(file) = &mut entry {
+            file.hash = Some(hash.to_string());
+        }
+        entry
+    }
+
+    #[test]
+    fn it_lists_the_changes_between_two_trees() {
+        let from = tree(vec![dir("/docs/"), file("/docs/a.txt/", 1), file("/b.txt/", 1)]);
+        let to = tree(vec![dir("/docs/"), file("/docs/a.txt/", 2), dir("/new/"), file("/new/c.txt/", 1)]);
+
+        let diff = Diff::between(&from, &to);
+
+        assert_eq!(diff.to_string(), "- /b.txt\n~ /docs/a.txt\n+ /new\n+ /new/c.txt\n");
+        assert!(Diff::between(&to, &to).is_empty());
+    }
+
+    #[test]
+    fn it_turns_the_old_tree_into_the_new_one() {
+        let mut from = tree(vec![dir("/docs/"), file("/docs/a.txt/", 1), file("/b/", 1)]);
+        let to = tree(vec![file("/docs/", 3), dir("/b/"), file("/b/c.txt/", 1)]);
+
+        for op in Diff::between(&from, &to).operations() {
+            op.apply(&mut from).unwrap();
+        }
+
+        assert!(Diff::between(&from, &to).is_empty());
+    }
+
+    #[test]
+    fn it_ignores_creation_times_and_file_types() {
+        let mut copy = file("/a.txt/", 1);
+        copy.set_created_at(SystemTime::now());
+        copy.set_permission("100644".to_string());
+
+        assert!(Diff::between(&tree(vec![file("/a.txt/", 1)]), &tree(vec![copy])).is_empty());
+    }
+
+    #[test]
+    fn it_matches_moved_files_by_content() {
+        let mut from = tree(vec![dir("/old/"), stored("/old/a.txt/", "aaa"), stored("/b.txt/", "bbb")]);
+        let mut b = stored("/b.txt/", "bbb");
+        b.set_permission("600".to_string());
+        let to = tree(vec![dir("/new/"), stored("/new/renamed.txt/", "aaa"), b]);
+
+        let diff = Diff::between(&from, &to);
+
+        assert_eq!(diff.to_string(), "* /b.txt 644 -> 600 0:0\n+ /new\n> /old/a.txt -> /new/renamed.txt\n- /old\n");
+        assert!(diff.operations().contains(&Operation::Rename {
+            from: "/old/a.txt/".to_string(),
+            to: "/new/renamed.txt/".to_string(),
+            exchange: false,
+        }));
+
+        for op in diff.operations() {
+            op.apply(&mut from).unwrap();
+        }
+        assert!(Diff::between(&from, &to).is_empty());
+    }
+}
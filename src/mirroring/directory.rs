@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::time::SystemTime;
 
 use serde::{Deserialize, Serialize};
@@ -21,6 +22,11 @@ pub struct Directory {
 
     #[serde(with = "serde_millis")]
     pub created_at: SystemTime,
+
+    /// The last modification time, trees published before it was tracked
+    /// default it to the UNIX epoch.
+    #[serde(with = "serde_millis", default = "super::entry::unknown_time")]
+    pub modified_at: SystemTime,
     
     /// The group id of the directory.
     pub gid: u32,
@@ -30,6 +36,10 @@ pub struct Directory {
 
     #[serde(rename = "type")]
     pub entry_type: EntryType,
+
+    /// Extended attributes by name, values are hex encoded.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub xattrs: BTreeMap<String, String>,
 }
 
 impl Directory {
@@ -47,9 +57,11 @@ impl Directory {
             path,
             permission,
             created_at,
+            modified_at: created_at,
             uid,
             gid,
             entry_type: EntryType::Directory,
+            xattrs: BTreeMap::new(),
         }
     }
 }
@@ -1,6 +1,9 @@
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
 use serde::{Deserialize, Serialize};
 
-use super::{sym_link::SymLink, Directory, EntryType, File};
+use super::{Directory, EntryType, File, SymLink};
 
 /// An entry in the mirroring.
 ///
@@ -56,6 +59,15 @@ impl Entry {
         }
     }
 
+    /// Sets the name of the entry.
+    pub fn set_name(&mut self, name: String) {
+        match self {
+            Entry::File(file) => file.name = name,
+            Entry::Directory(dir) => dir.name = name,
+            Entry::SymLink(link) => link.name = name,
+        }
+    }
+
     /// Returns the permission of the entry.
     pub fn permission(&self) -> &str {
         match self {
@@ -64,4 +76,110 @@ impl Entry {
             Entry::SymLink(link) => &link.permission,
         }
     }
+
+    /// Sets the permission of the entry, as an octal string.
+    pub fn set_permission(&mut self, permission: String) {
+        match self {
+            Entry::File(file) => file.permission = permission,
+            Entry::Directory(dir) => dir.permission = permission,
+            Entry::SymLink(link) => link.permission = permission,
+        }
+    }
+
+    /// Returns the user id and group id owning the entry.
+    pub fn owner(&self) -> (u32, u32) {
+        match self {
+            Entry::File(file) => (file.uid, file.gid),
+            Entry::Directory(dir) => (dir.uid, dir.gid),
+            Entry::SymLink(link) => (link.uid, link.gid),
+        }
+    }
+
+    /// Sets the user id and group id owning the entry.
+    pub fn set_owner(&mut self, uid: u32, gid: u32) {
+        match self {
+            Entry::File(file) => (file.uid, file.gid) = (uid, gid),
+            Entry::Directory(dir) => (dir.uid, dir.gid) = (uid, gid),
+            Entry::SymLink(link) => (link.uid, link.gid) = (uid, gid),
+        }
+    }
+
+    /// Returns the extended attributes of the entry.
+    pub fn xattrs(&self) -> &BTreeMap<String, String> {
+        match self {
+            Entry::File(file) => &file.xattrs,
+            Entry::Directory(dir) => &dir.xattrs,
+            Entry::SymLink(link) => &link.xattrs,
+        }
+    }
+
+    /// Sets the extended attributes of the entry.
+    pub fn set_xattrs(&mut self, xattrs: BTreeMap<String, String>) {
+        match self {
+            Entry::File(file) => file.xattrs = xattrs,
+            Entry::Directory(dir) => dir.xattrs = xattrs,
+            Entry::SymLink(link) => link.xattrs = xattrs,
+        }
+    }
+
+    /// Returns the last modification time of the entry.
+    ///
+    /// Falls back to the creation time for entries published before the
+    /// modification time was tracked.
+    pub fn modified_at(&self) -> SystemTime {
+        let (created_at, modified_at) = match self {
+            Entry::File(file) => (file.created_at, file.modified_at),
+            Entry::Directory(dir) => (dir.created_at, dir.modified_at),
+            Entry::SymLink(link) => (link.created_at, link.modified_at),
+        };
+
+        match modified_at == unknown_time() {
+            true => created_at,
+            false => modified_at,
+        }
+    }
+
+    /// Sets the creation time of the entry.
+    pub fn set_created_at(&mut self, created_at: SystemTime) {
+        match self {
+            Entry::File(file) => file.created_at = created_at,
+            Entry::Directory(dir) => dir.created_at = created_at,
+            Entry::SymLink(link) => link.created_at = created_at,
+        }
+    }
+
+    /// Sets the last modification time of the entry.
+    pub fn set_modified_at(&mut self, modified_at: SystemTime) {
+        match self {
+            Entry::File(file) => file.modified_at = modified_at,
+            Entry::Directory(dir) => dir.modified_at = modified_at,
+            Entry::SymLink(link) => link.modified_at = modified_at,
+        }
+    }
+}
+
+/// Default modification time of entries published before it was tracked.
+pub(super) fn unknown_time() -> SystemTime {
+    SystemTime::UNIX_EPOCH
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_falls_back_to_the_creation_time_for_older_entries() {
+        let entry: Entry = serde_json::from_str(r#"{
+            "name": "file.txt",
+            "path": "/file.txt/",
+            "type": "file",
+            "gid": 0,
+            "uid": 0,
+            "created_at": 1708731936000,
+            "size": 12,
+            "permission": "100644"
+        }"#).unwrap();
+
+        assert_eq!(entry.modified_at(), SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(1708731936000));
+    }
 }
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::time::SystemTime;
 
 use serde::{Deserialize, Serialize};
@@ -9,7 +10,8 @@ use super::EntryType;
 /// It stores UNIX-like file system information about a file.
 ///
 /// This struct does not store the content of the file.
-/// It assumes the caller library has an access to the content using the path.
+/// The content is stored in the Aleph storage and referenced by its `hash`,
+/// or by its `chunks` when it is split in several pieces.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct File {
     pub name: String,
@@ -26,6 +28,11 @@ pub struct File {
     #[serde(with = "serde_millis")]
     pub created_at: SystemTime,
 
+    /// The last modification time, trees published before it was tracked
+    /// default it to the UNIX epoch.
+    #[serde(with = "serde_millis", default = "super::entry::unknown_time")]
+    pub modified_at: SystemTime,
+
     /// The group id of the directory.
     pub gid: u32,
 
@@ -34,6 +41,29 @@ pub struct File {
 
     #[serde(rename = "type")]
     pub entry_type: EntryType,
+
+    /// Extended attributes by name, values are hex encoded.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub xattrs: BTreeMap<String, String>,
+
+    /// The hash of the file content in the Aleph storage.
+    ///
+    /// It is `None` as long as no content has been uploaded for the file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+
+    /// The pieces of the file content in the Aleph storage, in order.
+    ///
+    /// It is empty when the content is stored whole under `hash`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<Chunk>,
+
+    /// Identifier shared by the hard links of a file.
+    ///
+    /// All the entries with the same `link_id` are paths to a single file
+    /// and reference the same content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_id: Option<String>,
 }
 
 impl File {
@@ -53,9 +83,54 @@ impl File {
             size,
             permission,
             created_at,
+            modified_at: created_at,
             uid,
             gid,
             entry_type: EntryType::File,
+            xattrs: BTreeMap::new(),
+            hash: None,
+            chunks: vec![],
+            link_id: None,
+        }
+    }
+
+    /// Returns the pieces of the file content, in order.
+    pub fn content(&self) -> Vec<Chunk> {
+        match &self.hash {
+            Some(hash) => vec![Chunk {
+                hash: hash.clone(),
+                size: self.size,
+            }],
+            None => self.chunks.clone(),
         }
     }
+
+    /// References the content stored as `chunks`.
+    ///
+    /// A content stored in a single piece is referenced by `hash`, as files
+    /// were before they could be split.
+    pub fn set_content(&mut self, mut chunks: Vec<Chunk>) {
+        self.size = chunks.iter().map(|chunk| chunk.size).sum();
+
+        match chunks.len() {
+            1 => {
+                self.hash = chunks.pop().map(|chunk| chunk.hash);
+                self.chunks = vec![];
+            },
+            _ => {
+                self.hash = None;
+                self.chunks = chunks;
+            }
+        }
+    }
+}
+
+/// A piece of the content of a file, stored on its own in the Aleph storage.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Chunk {
+    /// The hash of the piece in the Aleph storage.
+    pub hash: String,
+
+    /// The size of the piece in bytes.
+    pub size: u64,
 }
@@ -1,6 +1,22 @@
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{Directory, Entry, File, SymLink};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum FSTreeError {
+    #[error("no entry found at {0}")]
+    NotFound(String),
 
-use super::{directory::Directory, entry::Entry, file::File, sym_link::SymLink};
+    #[error("directory {0} is not empty")]
+    NotEmpty(String),
+
+    #[error("an entry already exists at {0}")]
+    AlreadyExists(String),
+}
 
 /// A File System Tree abstraction.
 ///
@@ -8,12 +24,51 @@ use super::{directory::Directory, entry::Entry, file::File, sym_link::SymLink};
 /// essential metadatas to mirror the host tree.
 ///
 /// The entries are stored as a list of `Entry` which can be either a `File`,
-/// `Directory` or `Symlink`.
+/// `Directory` or `Symlink`, indexed by path.
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(from = "FSTreeData")]
 pub struct FSTree {
     root: String,
 
-    pub entries: Vec<Entry>,
+    entries: Vec<Entry>,
+
+    /// Time of the last operation applied to the tree.
+    #[serde(with = "serde_millis", default = "super::entry::unknown_time")]
+    updated_at: SystemTime,
+
+    /// Sequence number of the last batch of operations applied from every
+    /// author.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    heads: BTreeMap<String, u64>,
+
+    /// Position of the entries in `entries` by path.
+    #[serde(skip)]
+    index: BTreeMap<String, usize>,
+}
+
+/// The serialized form of a `FSTree`, from which the index is rebuilt.
+#[derive(Deserialize)]
+struct FSTreeData {
+    root: String,
+    entries: Vec<Entry>,
+    #[serde(with = "serde_millis", default = "super::entry::unknown_time")]
+    updated_at: SystemTime,
+    #[serde(default)]
+    heads: BTreeMap<String, u64>,
+}
+
+impl From<FSTreeData> for FSTree {
+    fn from(data: FSTreeData) -> Self {
+        let mut fs_tree = FSTree::new(data.root);
+        fs_tree.updated_at = data.updated_at;
+        fs_tree.heads = data.heads;
+
+        for entry in data.entries {
+            fs_tree.add_entry(entry);
+        }
+
+        fs_tree
+    }
 }
 
 /// The `FSTree` implementation.
@@ -33,9 +88,13 @@ impl FSTree {
         FSTree {
             root,
             entries: Vec::new(),
+            updated_at: super::entry::unknown_time(),
+            heads: BTreeMap::new(),
+            index: BTreeMap::new(),
         }
     }
 
+    #[allow(dead_code)]
     pub fn truncate_root(&self, path: &str) -> String {
         if path.starts_with(self.root.as_str()) {
             return path.strip_prefix(self.root.as_str()).unwrap().to_string();
@@ -46,9 +105,217 @@ impl FSTree {
         format!("/{path}")
     }
 
+    /// Returns the time of the last operation applied to the `FSTree`.
+    pub fn updated_at(&self) -> SystemTime {
+        self.updated_at
+    }
+
+    /// Sets the time of the last operation applied to the `FSTree`.
+    pub fn set_updated_at(&mut self, updated_at: SystemTime) {
+        self.updated_at = updated_at;
+    }
+
+    /// Returns the version of the `FSTree`, as the sequence number of the
+    /// last batch of operations applied from every author.
+    pub fn heads(&self) -> &BTreeMap<String, u64> {
+        &self.heads
+    }
+
+    /// Returns the sequence number of the last batch of operations applied
+    /// from an author, 0 when none was.
+    pub fn head(&self, author: &str) -> u64 {
+        self.heads.get(author).copied().unwrap_or(0)
+    }
+
+    /// Records a batch of operations from an author as applied.
+    pub fn set_head(&mut self, author: &str, seq: u64) {
+        let head = self.heads.entry(author.to_string()).or_insert(0);
+        *head = (*head).max(seq);
+    }
+
     /// Adds a new entry to the `FSTree`.
-    pub fn add_entry(&mut self, mut entry: Entry) {
-        self.entries.push(entry);
+    ///
+    /// An entry already stored at the same path is replaced.
+    pub fn add_entry(&mut self, entry: Entry) {
+        match self.index.get(entry.path()) {
+            Some(position) => {
+                self.entries[*position] = entry;
+            },
+            None => {
+                self.index.insert(entry.path().to_string(), self.entries.len());
+                self.entries.push(entry);
+            }
+        }
+    }
+
+    /// Returns the entry stored at the given path.
+    pub fn get_entry(&self, path: &str) -> Option<&Entry> {
+        self.index.get(path).map(|position| &self.entries[*position])
+    }
+
+    /// Returns the entry stored at the given path for modification.
+    ///
+    /// The path of the entry must not be changed, use `move_entry` instead.
+    pub fn get_entry_mut(&mut self, path: &str) -> Option<&mut Entry> {
+        self.index.get(path).map(|position| &mut self.entries[*position])
+    }
+
+    /// Returns the entries ordered by path, directories before their content.
+    pub fn get_entries_by_path(&self) -> impl Iterator<Item = &Entry> {
+        self.index.values().map(|position| &self.entries[*position])
+    }
+
+    /// Returns the entry stored at a path along with the entries below it,
+    /// ordered by path.
+    pub fn get_subtree<'a>(&'a self, path: &'a str) -> impl Iterator<Item = &'a Entry> {
+        self.index
+            .range::<str, _>((std::ops::Bound::Included(path), std::ops::Bound::Unbounded))
+            .take_while(move |(child, _)| child.starts_with(path))
+            .map(|(_, position)| &self.entries[*position])
+    }
+
+    /// Returns whether a directory path has entries below it.
+    pub fn has_children(&self, path: &str) -> bool {
+        self.index
+            .range::<str, _>((std::ops::Bound::Excluded(path), std::ops::Bound::Unbounded))
+            .next()
+            .is_some_and(|(child, _)| child.starts_with(path))
+    }
+
+    /// Returns the entries stored right below a directory path, by path.
+    pub fn get_children(&self, path: &str) -> Vec<&Entry> {
+        self.index
+            .range::<str, _>((std::ops::Bound::Excluded(path), std::ops::Bound::Unbounded))
+            .take_while(|(child, _)| child.starts_with(path))
+            .filter(|(child, _)| !child[path.len()..].trim_end_matches('/').contains('/'))
+            .map(|(_, position)| &self.entries[*position])
+            .collect()
+    }
+
+    /// Removes the entry stored at the given path from the `FSTree`.
+    ///
+    /// Directories are only removed once empty, as POSIX `rmdir` does, their
+    /// content has to be removed first.
+    pub fn remove_entry(&mut self, path: &str) -> Result<Entry, FSTreeError> {
+        let position = *self.index
+            .get(path)
+            .ok_or_else(|| FSTreeError::NotFound(path.to_string()))?;
+
+        if let Entry::Directory(_) = self.entries[position] {
+            if self.has_children(path) {
+                return Err(FSTreeError::NotEmpty(path.to_string()));
+            }
+        }
+
+        Ok(self.take_entry(position))
+    }
+
+    /// Removes the entry stored at the given path along with everything below it.
+    pub fn remove_subtree(&mut self, path: &str) {
+        let mut positions: Vec<usize> = self.index
+            .range::<str, _>((std::ops::Bound::Included(path), std::ops::Bound::Unbounded))
+            .take_while(|(child, _)| child.starts_with(path))
+            .map(|(_, position)| *position)
+            .collect();
+        positions.sort_unstable();
+
+        // Taking the last positions first keeps the others valid.
+        for position in positions.into_iter().rev() {
+            self.take_entry(position);
+        }
+    }
+
+    /// Takes the entry at a position out of `entries`, moving the last entry
+    /// in its place and updating the index of both.
+    fn take_entry(&mut self, position: usize) -> Entry {
+        let entry = self.entries.swap_remove(position);
+        self.index.remove(entry.path());
+
+        if let Some(moved) = self.entries.get(position) {
+            self.index.insert(moved.path().to_string(), position);
+        }
+
+        entry
+    }
+
+    /// Moves the entry stored at `from` to `to`.
+    ///
+    /// Directories are moved along with their whole content. Returns the
+    /// previous and new path of every moved entry.
+    pub fn move_entry(&mut self, from: &str, to: &str) -> Result<Vec<(String, String)>, FSTreeError> {
+        if self.get_entry(from).is_none() {
+            return Err(FSTreeError::NotFound(from.to_string()));
+        }
+        if self.get_entry(to).is_some() {
+            return Err(FSTreeError::AlreadyExists(to.to_string()));
+        }
+
+        Ok(self.rewrite_paths(|path| {
+            path.strip_prefix(from).map(|rest| format!("{to}{rest}"))
+        }))
+    }
+
+    /// Swaps the entries stored at `a` and `b`, along with their content.
+    ///
+    /// Returns the previous and new path of every moved entry.
+    pub fn exchange_entries(&mut self, a: &str, b: &str) -> Result<Vec<(String, String)>, FSTreeError> {
+        for path in [a, b] {
+            if self.get_entry(path).is_none() {
+                return Err(FSTreeError::NotFound(path.to_string()));
+            }
+        }
+
+        Ok(self.rewrite_paths(|path| {
+            match (path.strip_prefix(a), path.strip_prefix(b)) {
+                (Some(rest), _) => Some(format!("{b}{rest}")),
+                (_, Some(rest)) => Some(format!("{a}{rest}")),
+                _ => None,
+            }
+        }))
+    }
+
+    /// Applies a path mapping to every entry, renaming the entries it moves.
+    fn rewrite_paths<F>(&mut self, map: F) -> Vec<(String, String)>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut moved = Vec::new();
+
+        for (position, entry) in self.entries.iter_mut().enumerate() {
+            if let Some(path) = map(entry.path()) {
+                let name = path.trim_end_matches('/').rsplit('/').next().unwrap_or_default();
+
+                moved.push((entry.path().to_string(), path.clone(), position));
+                entry.set_name(name.to_string());
+                entry.set_path(path);
+            }
+        }
+
+        // Moved paths may be taken by other moved entries, as when exchanging.
+        for (from, _, _) in moved.iter() {
+            self.index.remove(from);
+        }
+        for (_, to, position) in moved.iter() {
+            self.index.insert(to.clone(), *position);
+        }
+
+        moved.into_iter().map(|(from, to, _)| (from, to)).collect()
+    }
+
+    /// Returns the number of bytes used by the files of the `FSTree`.
+    ///
+    /// Hard links share their content, which is only counted once.
+    pub fn used_bytes(&self) -> u64 {
+        let mut links = std::collections::BTreeSet::new();
+
+        self.get_files()
+            .into_iter()
+            .filter(|file| match &file.link_id {
+                Some(link_id) => links.insert(link_id),
+                None => true,
+            })
+            .map(|file| file.size)
+            .sum()
     }
 
     /// Returns the list of entries in the `FSTree`.
@@ -98,9 +365,12 @@ mod tests {
         os::unix::fs::{MetadataExt, PermissionsExt},
     };
 
+    // Used by the testdata tests disabled below.
+    #[allow(dead_code)]
     const TEST_DATA_DIR: &str = "./src/mirroring/testdata";
 
     /// Mock the implementation of a file system reader.
+    #[allow(dead_code)]
     fn get_tree(fs: &mut FSTree, path: &str) {
         let test_dir = fs::read_dir(path).unwrap();
 
@@ -164,6 +434,7 @@ mod tests {
     }
 
     /// Compare some fields of two vectors of entries.
+    #[allow(dead_code)]
     fn compare_entries(expected: &Vec<Entry>, actual: &Vec<Entry>) {
         assert_eq!(
             expected.len(),
@@ -217,6 +488,133 @@ mod tests {
         }
     }
 
+    fn test_tree() -> FSTree {
+        let now = std::time::SystemTime::now();
+        let mut fs = FSTree::new("/".to_string());
+
+        fs.add_entry(Entry::Directory(Directory::new("docs".to_string(), "/docs/".to_string(), "755".to_string(), now, 0, 0)));
+        fs.add_entry(Entry::File(File::new("a.txt".to_string(), "/docs/a.txt/".to_string(), 4, "644".to_string(), now, 0, 0)));
+        fs.add_entry(Entry::Directory(Directory::new("empty".to_string(), "/empty/".to_string(), "755".to_string(), now, 0, 0)));
+
+        fs
+    }
+
+    #[test]
+    fn it_removes_a_file() {
+        let mut fs = test_tree();
+
+        let removed = fs.remove_entry("/docs/a.txt/").unwrap();
+
+        assert_eq!(removed.name(), "a.txt");
+        assert!(fs.get_entry("/docs/a.txt/").is_none());
+        assert_eq!(fs.get_entries().len(), 2);
+    }
+
+    #[test]
+    fn it_removes_only_empty_directories() {
+        let mut fs = test_tree();
+
+        assert_eq!(fs.remove_entry("/docs/"), Err(FSTreeError::NotEmpty("/docs/".to_string())));
+        assert!(fs.remove_entry("/empty/").is_ok());
+
+        fs.remove_entry("/docs/a.txt/").unwrap();
+        assert!(fs.remove_entry("/docs/").is_ok());
+        assert!(fs.get_entries().is_empty());
+    }
+
+    #[test]
+    fn it_fails_to_remove_a_missing_entry() {
+        let mut fs = test_tree();
+
+        assert_eq!(fs.remove_entry("/missing/"), Err(FSTreeError::NotFound("/missing/".to_string())));
+    }
+
+    #[test]
+    fn it_moves_a_directory_with_its_content() {
+        let mut fs = test_tree();
+
+        let moved = fs.move_entry("/docs/", "/empty/notes/").unwrap();
+
+        assert_eq!(moved, vec![
+            ("/docs/".to_string(), "/empty/notes/".to_string()),
+            ("/docs/a.txt/".to_string(), "/empty/notes/a.txt/".to_string()),
+        ]);
+        assert_eq!(fs.get_entry("/empty/notes/").unwrap().name(), "notes");
+        assert_eq!(fs.get_entry("/empty/notes/a.txt/").unwrap().name(), "a.txt");
+        assert!(fs.get_entry("/docs/").is_none());
+    }
+
+    #[test]
+    fn it_refuses_to_move_over_an_existing_entry() {
+        let mut fs = test_tree();
+
+        assert_eq!(fs.move_entry("/docs/", "/empty/"), Err(FSTreeError::AlreadyExists("/empty/".to_string())));
+        assert_eq!(fs.move_entry("/missing/", "/other/"), Err(FSTreeError::NotFound("/missing/".to_string())));
+    }
+
+    #[test]
+    fn it_exchanges_two_entries() {
+        let mut fs = test_tree();
+
+        fs.exchange_entries("/docs/", "/empty/").unwrap();
+
+        assert_eq!(fs.get_entry("/docs/a.txt/"), None);
+        assert_eq!(fs.get_entry("/empty/a.txt/").unwrap().name(), "a.txt");
+        assert_eq!(fs.get_entry("/empty/").unwrap().name(), "empty");
+        assert_eq!(fs.get_entries().len(), 3);
+    }
+
+    #[test]
+    fn it_indexes_entries_by_path() {
+        let mut fs = test_tree();
+        fs.remove_subtree("/docs/");
+
+        let json = serde_json::to_string(&fs).unwrap();
+        let mut fs: FSTree = serde_json::from_str(&json).unwrap();
+
+        assert!(fs.get_entry("/docs/a.txt/").is_none());
+        assert!(!fs.has_children("/empty/"));
+
+        fs.add_entry(Entry::File(File::new("b.txt".to_string(), "/empty/b.txt/".to_string(), 8, "644".to_string(), std::time::SystemTime::now(), 0, 0)));
+        if let Some(Entry::File(file)) = fs.get_entry_mut("/empty/b.txt/") {
+            file.size = 2;
+        }
+
+        assert!(fs.has_children("/empty/"));
+        assert_eq!(fs.get_children("/").iter().map(|entry| entry.path()).collect::<Vec<_>>(), vec!["/empty/"]);
+        assert_eq!(fs.used_bytes(), 2);
+        assert_eq!(fs.get_entries().len(), 2);
+    }
+
+    #[test]
+    fn it_keeps_the_index_in_step_with_removals() {
+        let mut fs = test_tree();
+        fs.add_entry(Entry::File(File::new("b.txt".to_string(), "/docs/b.txt/".to_string(), 2, "644".to_string(), std::time::SystemTime::now(), 0, 0)));
+        fs.add_entry(Entry::File(File::new("c.txt".to_string(), "/empty/c.txt/".to_string(), 2, "644".to_string(), std::time::SystemTime::now(), 0, 0)));
+
+        fs.remove_entry("/docs/a.txt/").unwrap();
+        fs.exchange_entries("/docs/", "/empty/").unwrap();
+        fs.remove_subtree("/docs/");
+
+        for entry in fs.get_entries() {
+            assert_eq!(fs.get_entry(entry.path()), Some(entry));
+        }
+        assert_eq!(fs.get_entries_by_path().map(|entry| entry.path()).collect::<Vec<_>>(), vec!["/empty/", "/empty/b.txt/"]);
+    }
+
+    #[test]
+    fn it_counts_hard_linked_content_once() {
+        let mut fs = test_tree();
+        let mut link = File::new("b.txt".to_string(), "/b.txt/".to_string(), 4, "644".to_string(), std::time::SystemTime::now(), 0, 0);
+        link.link_id = Some("a".to_string());
+        fs.add_entry(Entry::File(link.clone()));
+
+        link.path = "/docs/b.txt/".to_string();
+        fs.add_entry(Entry::File(link));
+
+        assert_eq!(fs.used_bytes(), 8);
+    }
+
     /*
     #[test]
     fn simple_dir_with_one_file() {
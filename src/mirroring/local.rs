@@ -0,0 +1,120 @@
+use std::fs;
+use std::io;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use super::{Chunk, Directory, Entry, FSTree, File, SymLink};
+
+/// Reads a local directory into a `FSTree`.
+///
+/// Entries are named by their path relative to `dir`. `content` returns the
+/// pieces of the content of a file, given its local path and its entry, as
+/// they are stored in the Aleph storage so that the tree can be compared with
+/// a published one. Entries other than
+/// files, directories and symbolic links are skipped.
+pub fn read_local_tree<F>(dir: &Path, mut content: F) -> io::Result<FSTree>
+where
+    F: FnMut(&Path, &File) -> io::Result<Vec<Chunk>>,
+{
+    let mut fs_tree = FSTree::new("/".to_string());
+    read_entries(&mut fs_tree, dir, "/", &mut content)?;
+
+    Ok(fs_tree)
+}
+
+/// Adds the local entry found at `local_path` to a `FSTree`, at `path`.
+///
+/// The entries below a directory are added too when `recursive` is set.
+/// Nothing is added when there is no such local entry.
+pub fn read_local_entry<F>(fs_tree: &mut FSTree, local_path: &Path, path: &str, recursive: bool, mut content: F) -> io::Result<()>
+where
+    F: FnMut(&Path, &File) -> io::Result<Vec<Chunk>>,
+{
+    match read_entry(fs_tree, local_path, path, recursive, &mut content) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound && fs::symlink_metadata(local_path).is_err() => Ok(()),
+        result => result,
+    }
+}
+
+/// Adds the entries of the local directory `dir`, mirrored at `path`.
+fn read_entries<F>(fs_tree: &mut FSTree, dir: &Path, path: &str, content: &mut F) -> io::Result<()>
+where
+    F: FnMut(&Path, &File) -> io::Result<Vec<Chunk>>,
+{
+    let mut dir_entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    dir_entries.sort_by_key(|dir_entry| dir_entry.file_name());
+
+    for dir_entry in dir_entries {
+        let name = dir_entry.file_name().to_string_lossy().into_owned();
+        read_entry(fs_tree, &dir_entry.path(), &format!("{}{}/", path, name), true, content)?;
+    }
+
+    Ok(())
+}
+
+/// Adds the local entry `local_path`, mirrored at `entry_path`.
+fn read_entry<F>(fs_tree: &mut FSTree, local_path: &Path, entry_path: &str, recursive: bool, content: &mut F) -> io::Result<()>
+where
+    F: FnMut(&Path, &File) -> io::Result<Vec<Chunk>>,
+{
+    let metadata = fs::symlink_metadata(local_path)?;
+    let name = entry_path.trim_end_matches('/').rsplit('/').next().unwrap_or_default().to_string();
+    let entry_path = entry_path.to_string();
+    let permission = format!("{:o}", metadata.permissions().mode() & 0o7777);
+    // Mounts date new entries with their modification time, so do we.
+    // Trees keep times to the millisecond.
+    let millis = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    let modified_at = UNIX_EPOCH + Duration::from_millis(millis as u64);
+
+    if metadata.is_dir() {
+        let dir = Directory::new(name, entry_path.clone(), permission, modified_at, metadata.gid(), metadata.uid());
+        fs_tree.add_entry(Entry::Directory(dir));
+
+        if recursive {
+            read_entries(fs_tree, local_path, &entry_path, content)?;
+        }
+    } else if metadata.is_file() {
+        let mut file = File::new(name, entry_path, metadata.size(), permission, modified_at, metadata.gid(), metadata.uid());
+        file.set_content(content(local_path, &file)?);
+
+        fs_tree.add_entry(Entry::File(file));
+    } else if metadata.file_type().is_symlink() {
+        let link_to = fs::read_link(local_path)?.to_string_lossy().into_owned();
+        let link = SymLink::new(name, entry_path, permission, modified_at, metadata.gid(), metadata.uid(), link_to);
+
+        fs_tree.add_entry(Entry::SymLink(link));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_a_local_directory() {
+        let dir = std::env::temp_dir().join(format!("fs0x-local-{}", std::process::id()));
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        fs::write(dir.join("docs/a.txt"), b"hello").unwrap();
+        std::os::unix::fs::symlink("docs/a.txt", dir.join("latest")).unwrap();
+
+        let fs_tree = read_local_tree(&dir, |path, file| Ok(vec![Chunk {
+            hash: path.file_name().unwrap().to_string_lossy().into_owned(),
+            size: file.size,
+        }]));
+
+        fs::remove_dir_all(&dir).unwrap();
+        let fs_tree = fs_tree.unwrap();
+
+        let paths: Vec<&str> = fs_tree.get_entries().iter().map(|entry| entry.path()).collect();
+        assert_eq!(paths, vec!["/docs/", "/docs/a.txt/", "/latest/"]);
+        assert_eq!(fs_tree.get_files()[0].hash.as_deref(), Some("a.txt"));
+        assert_eq!(fs_tree.get_files()[0].size, 5);
+        assert_eq!(fs_tree.get_symlinks()[0].link_to, "docs/a.txt");
+
+        let published: FSTree = serde_json::from_str(&serde_json::to_string(&fs_tree).unwrap()).unwrap();
+        assert_eq!(published.get_entries(), fs_tree.get_entries());
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use super::{Entry, FSTree, Operation, OperationBatch};
+
+/// A change of a peer made concurrently with the change of another peer.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Conflict {
+    /// Path both peers changed.
+    pub path: String,
+
+    /// Address of the peer whose change was set aside.
+    pub author: String,
+
+    /// Path the change of the peer was kept at, `None` when it was dropped.
+    pub kept_at: Option<String>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kept_at {
+            Some(kept_at) => write!(f, "concurrent change of {} by {} kept at {}", self.path, self.author, kept_at),
+            None => write!(f, "concurrent deletion of {} by {} dropped", self.path, self.author),
+        }
+    }
+}
+
+/// A batch already replayed, with the paths it changed.
+struct Replayed {
+    author: String,
+    seq: u64,
+    paths: BTreeSet<String>,
+}
+
+/// Replays batches of operations of every peer over a `FSTree`.
+///
+/// Batches are replayed in the order they were made, the ones the tree
+/// already saw are skipped. A batch whose parent version misses batches of
+/// other peers was made concurrently with them, its changes to the paths
+/// they changed are merged the same way by every peer:
+///
+/// - a file or symlink written by both is kept twice, the later version
+///   going to `name.conflict-<peer>`,
+/// - the deletion of an entry changed by the other peer is dropped,
+/// - anything else applies in order.
+///
+/// Returns the conflicts met.
+pub fn replay(fs_tree: &mut FSTree, mut batches: Vec<OperationBatch>) -> Vec<Conflict> {
+    batches.sort_by(|a, b| (a.at, &a.author, a.seq).cmp(&(b.at, &b.author, b.seq)));
+
+    let mut replayed: Vec<Replayed> = Vec::new();
+    let mut conflicts = Vec::new();
+
+    for mut batch in batches {
+        if batch.seq <= fs_tree.head(&batch.author) {
+            continue;
+        }
+
+        let concurrent: BTreeSet<&str> = replayed.iter()
+            .filter(|other| other.author != batch.author)
+            .filter(|other| other.seq > batch.parent.get(&other.author).copied().unwrap_or(0))
+            .flat_map(|other| other.paths.iter().map(String::as_str))
+            .collect();
+
+        let mut paths = BTreeSet::new();
+        let mut ops = Vec::new();
+
+        for op in std::mem::take(&mut batch.ops) {
+            paths.extend(op.paths().into_iter().map(str::to_string));
+
+            match op {
+                Operation::Create { entry } | Operation::Setattr { entry }
+                    if !matches!(entry, Entry::Directory(_))
+                        && concurrent.contains(entry.path())
+                        && fs_tree.get_entry(entry.path()).is_some_and(|current| *current != entry) =>
+                {
+                    let path = entry.path().to_string();
+                    let entry = conflict_copy(entry, &batch.author);
+
+                    conflicts.push(Conflict {
+                        path,
+                        author: batch.author.clone(),
+                        kept_at: Some(entry.path().to_string()),
+                    });
+                    ops.push(Operation::Create { entry });
+                },
+                Operation::Delete { path } if concurrent.iter().any(|other| other.starts_with(path.as_str())) => {
+                    conflicts.push(Conflict {
+                        path,
+                        author: batch.author.clone(),
+                        kept_at: None,
+                    });
+                },
+                op => ops.push(op),
+            }
+        }
+
+        batch.ops = ops;
+        batch.apply(fs_tree);
+
+        replayed.push(Replayed {
+            author: batch.author,
+            seq: batch.seq,
+            paths,
+        });
+    }
+
+    conflicts
+}
+
+/// Moves an entry next to itself, at `name.conflict-<peer>`.
+fn conflict_copy(mut entry: Entry, author: &str) -> Entry {
+    let peer: String = author.trim_start_matches("0x").chars().take(8).collect();
+    let name = format!("{}.conflict-{}", entry.name(), peer);
+    let path = format!("{}{}/", entry.path().trim_end_matches('/').strip_suffix(entry.name()).unwrap_or("/"), name);
+
+    // The copy is a file of its own, not another name of the original.
+    if let Entry::File(file) = &mut entry {
+        file.link_id = None;
+    }
+    entry.set_name(name);
+    entry.set_path(path);
+
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirroring::File;
+    use std::collections::BTreeMap;
+    use std::time::{Duration, SystemTime};
+
+    fn file(path: &str, size: u64) -> Entry {
+        let name = path.trim_end_matches('/').rsplit('/').next().unwrap();
+
+        Entry::File(File::new(name.to_string(), path.to_string(), size, "644".to_string(), SystemTime::UNIX_EPOCH, 0, 0))
+    }
+
+    fn batch(author: &str, seq: u64, parent: &[(&str, u64)], at: u64, ops: Vec<Operation>) -> OperationBatch {
+        let parent = parent.iter().map(|(author, seq)| (author.to_string(), *seq)).collect::<BTreeMap<_, _>>();
+        let mut batch = OperationBatch::new(author.to_string(), seq, parent, ops);
+        batch.at = SystemTime::UNIX_EPOCH + Duration::from_secs(at);
+
+        batch
+    }
+
+    #[test]
+    fn it_keeps_both_versions_of_a_concurrent_write() {
+        let mut fs_tree = FSTree::new("/".to_string());
+
+        let conflicts = replay(&mut fs_tree, vec![
+            batch("0xbbbbbbbbbb", 1, &[("0xaaaaaaaaaa", 1)], 3, vec![Operation::Setattr { entry: file("/docs/a.txt/", 2) }]),
+            batch("0xaaaaaaaaaa", 1, &[], 1, vec![Operation::Create { entry: file("/docs/a.txt/", 1) }]),
+            batch("0xaaaaaaaaaa", 2, &[("0xaaaaaaaaaa", 1)], 2, vec![Operation::Setattr { entry: file("/docs/a.txt/", 4) }]),
+        ]);
+
+        assert_eq!(conflicts, vec![Conflict {
+            path: "/docs/a.txt/".to_string(),
+            author: "0xbbbbbbbbbb".to_string(),
+            kept_at: Some("/docs/a.txt.conflict-bbbbbbbb/".to_string()),
+        }]);
+        assert_eq!(fs_tree.get_entry("/docs/a.txt/"), Some(&file("/docs/a.txt/", 4)));
+        assert_eq!(fs_tree.get_entry("/docs/a.txt.conflict-bbbbbbbb/").unwrap().name(), "a.txt.conflict-bbbbbbbb");
+        assert_eq!(fs_tree.head("0xaaaaaaaaaa"), 2);
+        assert_eq!(fs_tree.head("0xbbbbbbbbbb"), 1);
+    }
+
+    #[test]
+    fn it_drops_a_concurrent_deletion_of_a_changed_entry() {
+        let mut fs_tree = FSTree::new("/".to_string());
+        fs_tree.add_entry(file("/a.txt/", 1));
+
+        let conflicts = replay(&mut fs_tree, vec![
+            batch("0xa", 1, &[], 1, vec![Operation::Setattr { entry: file("/a.txt/", 2) }]),
+            batch("0xb", 1, &[], 2, vec![Operation::Delete { path: "/a.txt/".to_string() }]),
+        ]);
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].kept_at, None);
+        assert_eq!(fs_tree.get_entry("/a.txt/"), Some(&file("/a.txt/", 2)));
+    }
+
+    #[test]
+    fn it_applies_sequential_changes_without_conflict() {
+        let mut fs_tree = FSTree::new("/".to_string());
+
+        let batches = vec![
+            batch("0xa", 1, &[], 1, vec![Operation::Create { entry: file("/a.txt/", 1) }]),
+            batch("0xb", 1, &[("0xa", 1)], 2, vec![Operation::Setattr { entry: file("/a.txt/", 2) }]),
+        ];
+        let conflicts = replay(&mut fs_tree, batches.clone());
+
+        assert!(conflicts.is_empty());
+        assert_eq!(fs_tree.get_entry("/a.txt/"), Some(&file("/a.txt/", 2)));
+
+        // Replaying batches the tree already saw changes nothing.
+        assert!(replay(&mut fs_tree, batches).is_empty());
+        assert_eq!(fs_tree.get_entries().len(), 1);
+    }
+}
@@ -4,10 +4,18 @@ mod entry;
 mod file;
 mod directory;
 mod sym_link;
+mod operation;
+mod merge;
+mod diff;
+mod local;
 
 pub use entry::Entry;
 pub use entry_type::EntryType;
-pub use file::File;
+pub use file::{Chunk, File};
 pub use directory::Directory;
-pub use fs_tree::FSTree;
+pub use fs_tree::{FSTree, FSTreeError};
+pub use operation::{Operation, OperationBatch};
+pub use merge::{replay, Conflict};
+pub use diff::{Change, Diff};
+pub use local::{read_local_entry, read_local_tree};
 pub use sym_link::SymLink;
\ No newline at end of file
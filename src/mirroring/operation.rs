@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Entry, FSTree, FSTreeError};
+
+/// A change made to a `FSTree`.
+///
+/// Operations are posted on the channel of the mirroring instead of the whole
+/// tree, peers replay them in order over the latest snapshot of the tree.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    /// A file, symlink or hard link was created.
+    Create { entry: Entry },
+    /// A directory was created.
+    Mkdir { entry: Entry },
+    /// An entry was removed along with its content.
+    Delete { path: String },
+    /// An entry was moved, or swapped with the one at `to` on an exchange.
+    Rename {
+        from: String,
+        to: String,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        exchange: bool,
+    },
+    /// The attributes or the content of an entry changed.
+    Setattr { entry: Entry },
+}
+
+impl Operation {
+    /// Returns the paths the operation changes.
+    pub fn paths(&self) -> Vec<&str> {
+        match self {
+            Operation::Create { entry } | Operation::Mkdir { entry } | Operation::Setattr { entry } => vec![entry.path()],
+            Operation::Delete { path } => vec![path],
+            Operation::Rename { from, to, .. } => vec![from, to],
+        }
+    }
+
+    /// Applies the operation to a `FSTree`.
+    ///
+    /// Operations already applied leave the tree as is, a `Create` or a
+    /// `Setattr` replacing the entry stored at the same path.
+    pub fn apply(&self, fs_tree: &mut FSTree) -> Result<(), FSTreeError> {
+        match self {
+            Operation::Create { entry } | Operation::Mkdir { entry } | Operation::Setattr { entry } => {
+                fs_tree.add_entry(entry.clone());
+            },
+            Operation::Delete { path } => {
+                fs_tree.remove_subtree(path);
+            },
+            Operation::Rename { from, to, exchange: false } => {
+                // A rename over an existing entry replaces it.
+                if fs_tree.get_entry(from).is_some() {
+                    fs_tree.remove_subtree(to);
+                }
+                fs_tree.move_entry(from, to)?;
+            },
+            Operation::Rename { from, to, exchange: true } => {
+                fs_tree.exchange_entries(from, to)?;
+            },
+        }
+
+        Ok(())
+    }
+}
+
+/// A batch of operations posted at once, in the order they were made.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct OperationBatch {
+    /// When the operations were made, orders the batches of all the peers.
+    #[serde(with = "serde_millis")]
+    pub at: SystemTime,
+
+    /// Address of the peer which made the operations.
+    pub author: String,
+
+    /// Number of the batch among the batches of its author, strictly
+    /// increasing. Peers use the posting time in Unix milliseconds, bumped
+    /// past the last batch they know of, so it keeps increasing across mounts.
+    pub seq: u64,
+
+    /// Version of the `FSTree` the operations were made on, as the last
+    /// batch seen from every author.
+    #[serde(default)]
+    pub parent: BTreeMap<String, u64>,
+
+    pub ops: Vec<Operation>,
+}
+
+impl OperationBatch {
+    pub fn new(author: String, seq: u64, parent: BTreeMap<String, u64>, ops: Vec<Operation>) -> Self {
+        OperationBatch {
+            at: SystemTime::now(),
+            author,
+            seq,
+            parent,
+            ops,
+        }
+    }
+
+    /// Replays the batch over a `FSTree` and marks it as seen by the tree.
+    ///
+    /// Operations that do not apply, such as the rename of an entry a peer
+    /// already removed, are skipped.
+    pub fn apply(&self, fs_tree: &mut FSTree) {
+        for op in &self.ops {
+            if let Err(e) = op.apply(fs_tree) {
+                eprintln!("skipping operation {:?}: {}", op, e);
+            }
+        }
+
+        fs_tree.set_head(&self.author, self.seq);
+        fs_tree.set_updated_at(self.at);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirroring::{Directory, File};
+
+    #[test]
+    fn it_replays_operations_over_a_tree() {
+        let now = SystemTime::now();
+        let docs = Entry::Directory(Directory::new("docs".to_string(), "/docs/".to_string(), "755".to_string(), now, 0, 0));
+        let file = Entry::File(File::new("a.txt".to_string(), "/a.txt/".to_string(), 4, "644".to_string(), now, 0, 0));
+
+        let batch = OperationBatch::new("0xabc".to_string(), 1, BTreeMap::new(), vec![
+            Operation::Mkdir { entry: docs },
+            Operation::Create { entry: file },
+            Operation::Rename { from: "/a.txt/".to_string(), to: "/docs/a.txt/".to_string(), exchange: false },
+            Operation::Rename { from: "/missing/".to_string(), to: "/other/".to_string(), exchange: false },
+        ]);
+
+        let json = serde_json::to_string(&batch).unwrap();
+        let batch: OperationBatch = serde_json::from_str(&json).unwrap();
+
+        let mut fs_tree = FSTree::new("/".to_string());
+        batch.apply(&mut fs_tree);
+
+        assert_eq!(fs_tree.get_entry("/docs/a.txt/").unwrap().name(), "a.txt");
+        assert!(fs_tree.get_entry("/a.txt/").is_none());
+        assert_eq!(fs_tree.updated_at(), batch.at);
+        assert_eq!(fs_tree.head("0xabc"), 1);
+
+        Operation::Delete { path: "/docs/".to_string() }.apply(&mut fs_tree).unwrap();
+
+        assert!(fs_tree.get_entries().is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::time::SystemTime;
 
 use serde::{Deserialize, Serialize};
@@ -22,6 +23,11 @@ pub struct SymLink {
     #[serde(with = "serde_millis")]
     pub created_at: SystemTime,
 
+    /// The last modification time, trees published before it was tracked
+    /// default it to the UNIX epoch.
+    #[serde(with = "serde_millis", default = "super::entry::unknown_time")]
+    pub modified_at: SystemTime,
+
     /// The group id of the directory.
     pub gid: u32,
 
@@ -31,6 +37,10 @@ pub struct SymLink {
     #[serde(rename = "type")]
     pub entry_type: EntryType,
 
+    /// Extended attributes by name, values are hex encoded.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub xattrs: BTreeMap<String, String>,
+
     /// The path this symbolic link is linked to.
     pub link_to: String
 }
@@ -51,9 +61,11 @@ impl SymLink {
             path,
             permission,
             created_at,
+            modified_at: created_at,
             uid,
             gid,
             entry_type: EntryType::SymLink,
+            xattrs: BTreeMap::new(),
             link_to
         }
     }
@@ -0,0 +1,22 @@
+use crate::http::HttpClient;
+
+use self::v0::*;
+
+pub mod v0;
+
+#[derive(Clone)]
+pub struct AccountSDK {
+    v0: AccountSDKV0,
+}
+
+impl AccountSDK {
+    pub fn new(client: HttpClient) -> Self {
+        AccountSDK {
+            v0: AccountSDKV0::new(client),
+        }
+    }
+
+    pub fn v0(&self) -> &AccountSDKV0 {
+        &self.v0
+    }
+}
@@ -0,0 +1,65 @@
+pub mod res_objects;
+
+use std::collections::HashMap;
+
+use reqwest::Method;
+use thiserror::Error;
+
+use crate::http::{HttpClient, HttpClientError, Request};
+use self::res_objects::BalanceResponse;
+
+#[derive(Debug, Error)]
+pub enum AccountSDKV0Error {
+    #[error("http client encountered an error: {0}")]
+    Client(#[from] HttpClientError),
+
+    #[error("failed to deserialize response: {0}")]
+    ResponseDeserializationError(#[from] reqwest::Error),
+}
+
+#[derive(Clone)]
+pub struct AccountSDKV0 {
+    client: HttpClient,
+}
+
+impl AccountSDKV0 {
+    pub fn new(client: HttpClient) -> Self {
+        AccountSDKV0 { client }
+    }
+
+    /// Get the ALEPH balance of an address.
+    pub async fn balance(&self, address: &str) -> Result<BalanceResponse, AccountSDKV0Error> {
+        let req = Request {
+            method: Method::GET,
+            path: format!("/api/v0/addresses/{address}/balance"),
+            query_params: HashMap::new(),
+        };
+
+        let res = self.client.do_request(req).await?;
+
+        let data = res
+            .json::<BalanceResponse>()
+            .await
+            .map_err(AccountSDKV0Error::ResponseDeserializationError)?;
+
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_deserializes_a_balance() {
+        let balance: BalanceResponse = serde_json::from_str(r#"{
+            "address": "0x3f2d4bC0a9E8e5B1C4F6d9a3b7e4b1a8c2d5e6f7",
+            "balance": 1200.5,
+            "details": {"ETH": 1200.5},
+            "locked_amount": 200.0
+        }"#).unwrap();
+
+        assert_eq!(balance.balance, 1200.5);
+        assert_eq!(balance.locked_amount, 200.0);
+    }
+}
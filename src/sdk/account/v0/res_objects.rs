@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BalanceResponse {
+    /// The account's public address.
+    pub address: String,
+    /// The ALEPH balance of the account.
+    pub balance: f64,
+    /// The part of the balance locked by the account's resources.
+    #[serde(default)]
+    pub locked_amount: f64,
+}
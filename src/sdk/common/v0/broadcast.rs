@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use reqwest::Method;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::http::{HttpClient, HttpClientError, Request};
+
+use super::{BaseMessage, ItemType, MessageChain, MessageSigner, MessageSignerError, MessageType};
+
+/// The pubsub topic Aleph nodes listen to for incoming messages.
+const PUBSUB_TOPIC: &str = "ALEPH-TEST";
+
+#[derive(Debug, Error)]
+pub enum BroadcastError {
+    #[error("http client encountered an error: {0}")]
+    Client(#[from] HttpClientError),
+
+    #[error("failed to sign message: {0}")]
+    MessageSignError(#[from] MessageSignerError),
+}
+
+#[derive(Debug, Serialize)]
+pub struct PubSubNotifyRequest {
+    pub topic: String,
+    pub data: String,
+}
+
+impl<C: Serialize> BaseMessage<C> {
+    /// Creates an unsigned message sent by `sender` on the Ethereum chain.
+    ///
+    /// The item hash and content are left empty, use `set_inline_content` or
+    /// fill them from a storage upload before signing.
+    pub fn new(channel: String, message_type: MessageType, sender: String, time: f64, item_type: ItemType, content: C) -> Self {
+        BaseMessage {
+            channel,
+            time,
+            message_type,
+            chain: MessageChain::Ethereum,
+            sender,
+            hash_type: "sha256".to_string(),
+            item_hash: "".to_string(),
+            item_type,
+            content,
+            item_content: None,
+            signature: "".to_string(),
+        }
+    }
+
+    /// Stores the serialized content inline in the message and sets its hash.
+    pub fn set_inline_content(&mut self, content_json: String) {
+        let mut hasher = Sha256::new();
+        hasher.update(content_json.as_bytes());
+
+        self.item_type = ItemType::Inline;
+        self.item_hash = format!("{:x}", hasher.finalize());
+        self.item_content = Some(content_json);
+    }
+
+    /// Points the message at its content uploaded to the storage engine.
+    pub fn set_stored_content(&mut self, hash: String) {
+        self.item_type = ItemType::Storage;
+        self.item_hash = hash;
+        self.item_content = None;
+    }
+}
+
+/// Signs the message and publishes it on the Aleph pubsub topic.
+pub async fn sign_and_broadcast<C: Serialize, S: MessageSigner>(
+    client: &HttpClient,
+    signer: &S,
+    message: &mut BaseMessage<C>,
+) -> Result<(), BroadcastError> {
+    message.signature = signer.sign(message).await?;
+
+    let body = PubSubNotifyRequest {
+        topic: PUBSUB_TOPIC.to_string(),
+        data: serde_json::to_string(&message).unwrap(),
+    };
+
+    let req = Request {
+        method: Method::POST,
+        path: "/api/v0/ipfs/pubsub/pub".to_string(),
+        query_params: HashMap::new(),
+    };
+
+    client.do_post_request(req, body).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_sets_the_inline_content_and_hash() {
+        let mut message = BaseMessage::new(
+            "fsx".to_string(),
+            MessageType::Post,
+            "0x0".to_string(),
+            0.0,
+            ItemType::Storage,
+            42,
+        );
+
+        message.set_inline_content("42".to_string());
+
+        assert_eq!(message.item_type, ItemType::Inline);
+        assert_eq!(message.item_content.unwrap(), "42");
+        assert_eq!(message.item_hash, "73475cb40a568e8da8a045ced110137e159f890ac4da883b6b17dc651b3a8049");
+    }
+}
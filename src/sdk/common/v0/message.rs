@@ -34,7 +34,7 @@ impl MessageChain {
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub enum ItemType {
     #[serde(rename = "ipfs")]
-    IPFS,
+    Ipfs,
     #[serde(rename = "inline")]
     Inline,
     #[serde(rename = "storage")]
@@ -42,9 +42,10 @@ pub enum ItemType {
 }
 
 impl ItemType {
+    #[allow(dead_code)]
     pub fn as_str(&self) -> &str {
         match self {
-            ItemType::IPFS => "ipfs",
+            ItemType::Ipfs => "ipfs",
             ItemType::Inline => "inline",
             ItemType::Storage => "storage",
         }
@@ -61,6 +62,16 @@ pub struct PostContent<C> {
     pub time: f64,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StoreContent {
+    pub address: String,
+    /// The storage engine holding the stored content.
+    pub item_type: ItemType,
+    /// The hash of the stored content.
+    pub item_hash: String,
+    pub time: f64,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct BaseMessage<C> {
     /// The channel where the message was received.
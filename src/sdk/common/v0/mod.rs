@@ -1,7 +1,7 @@
+mod broadcast;
 mod message;
 mod signer;
-mod storage;
 
+pub use broadcast::*;
 pub use message::*;
-pub use signer::*;
-pub use storage::*;
\ No newline at end of file
+pub use signer::*;
\ No newline at end of file
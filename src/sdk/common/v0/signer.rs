@@ -1,15 +1,27 @@
-use std::fmt::format;
+use std::{path::Path, str::FromStr};
 
+use alloy_primitives::Address;
+use ethers::core::rand::thread_rng;
+use ethers::types::{Signature, SignatureError};
 use ethers_signers::{LocalWallet, Signer, WalletError};
 use serde::Serialize;
 use thiserror::Error;
 
-use super::BaseMessage;
+use super::{BaseMessage, MessageChain, MessageType};
 
 #[derive(Debug, Error)]
 pub enum MessageSignerError {
     #[error(transparent)]
     Error(#[from] WalletError),
+
+    #[error("invalid signer address: {0}")]
+    InvalidAddress(String),
+
+    #[error("invalid signature: {0}")]
+    InvalidSignature(#[from] SignatureError),
+
+    #[error("message of {sender} was signed by {signer}")]
+    SenderMismatch { sender: String, signer: String },
 }
 
 pub trait MessageSigner {
@@ -17,10 +29,42 @@ pub trait MessageSigner {
     fn get_address(&self) -> String;
 }
 
-fn get_verificatin_buffer<C: Serialize>(message: &BaseMessage<C>) -> String {
-    format!("{}\n{}\n{}\n{}", message.chain.as_str(), message.sender, message.message_type.as_str(), message.item_hash)
+/// Returns the EIP-55 checksummed address of the signer.
+pub fn get_checksum_address<S: MessageSigner>(signer: &S) -> Result<String, MessageSignerError> {
+    let address = signer.get_address();
+
+    match Address::from_str(address.as_str()) {
+        Ok(addr) => Ok(addr.to_checksum(None)),
+        Err(_) => Err(MessageSignerError::InvalidAddress(address)),
+    }
+}
+
+fn get_verification_buffer(chain: MessageChain, sender: &str, message_type: MessageType, item_hash: &str) -> String {
+    format!("{}\n{}\n{}\n{}", chain.as_str(), sender, message_type.as_str(), item_hash)
+}
+
+/// Checks that a message was signed by its sender.
+///
+/// The signer address is recovered from the signature of the message
+/// verification buffer.
+pub fn verify_signature(
+    chain: MessageChain,
+    sender: &str,
+    message_type: MessageType,
+    item_hash: &str,
+    signature: &str,
+) -> Result<(), MessageSignerError> {
+    let buffer = get_verification_buffer(chain, sender, message_type, item_hash);
+    let signature = Signature::from_str(signature.trim_start_matches("0x"))?;
+    let signer = format!("0x{}", hex::encode(signature.recover(buffer)?.as_bytes()));
+
+    match signer.eq_ignore_ascii_case(sender) {
+        true => Ok(()),
+        false => Err(MessageSignerError::SenderMismatch { sender: sender.to_string(), signer }),
+    }
 }
 
+#[derive(Clone)]
 pub struct DefaultEthereumSigner {
     signer: LocalWallet,
 }
@@ -33,11 +77,32 @@ impl DefaultEthereumSigner {
             signer
         })
     }
+
+    /// Decrypts the signing key from an encrypted JSON keystore.
+    pub fn from_keystore(path: &Path, password: &str) -> Result<Self, MessageSignerError> {
+        let signer = LocalWallet::decrypt_keystore(path, password)?;
+
+        Ok(DefaultEthereumSigner {
+            signer
+        })
+    }
+
+    /// Generates a signer with a new random key.
+    pub fn random() -> Self {
+        DefaultEthereumSigner {
+            signer: LocalWallet::new(&mut thread_rng()),
+        }
+    }
+
+    /// Returns the hex encoded private key of the signer.
+    pub fn private_key(&self) -> String {
+        format!("0x{}", hex::encode(self.signer.signer().to_bytes()))
+    }
 }
 
 impl MessageSigner for DefaultEthereumSigner {
     async fn sign<C: Serialize>(&self, message: &BaseMessage<C>) -> Result<String, MessageSignerError> {
-        let buffer = get_verificatin_buffer(message);
+        let buffer = get_verification_buffer(message.chain, &message.sender, message.message_type, &message.item_hash);
         let signature = self.signer.sign_message(buffer).await?;
 
         Ok(format!("0x{}", hex::encode(signature.to_vec())))
@@ -47,3 +112,50 @@ impl MessageSigner for DefaultEthereumSigner {
         format!("0x{}", hex::encode(self.signer.address().as_bytes()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_restores_a_random_signer_from_its_private_key() {
+        let signer = DefaultEthereumSigner::random();
+        let restored = DefaultEthereumSigner::new(signer.private_key()).unwrap();
+
+        assert_eq!(signer.get_address(), restored.get_address());
+    }
+
+    #[test]
+    fn it_verifies_the_sender_of_a_signed_message() {
+        let signer = DefaultEthereumSigner::random();
+        let other = DefaultEthereumSigner::random();
+        let address = get_checksum_address(&signer).unwrap();
+
+        let mut message = BaseMessage::new("fsx".to_string(), MessageType::Post, address.clone(), 0.0, crate::sdk::common::ItemType::Inline, "content");
+        message.item_hash = "hash".to_string();
+        let signature = tokio::runtime::Runtime::new().unwrap().block_on(signer.sign(&message)).unwrap();
+
+        assert!(verify_signature(MessageChain::Ethereum, &address, MessageType::Post, "hash", &signature).is_ok());
+        assert!(matches!(
+            verify_signature(MessageChain::Ethereum, &other.get_address(), MessageType::Post, "hash", &signature),
+            Err(MessageSignerError::SenderMismatch { .. })
+        ));
+        assert!(verify_signature(MessageChain::Ethereum, &address, MessageType::Post, "other", &signature).is_err());
+        assert!(verify_signature(MessageChain::Ethereum, &address, MessageType::Post, "hash", "0x00").is_err());
+    }
+
+    #[test]
+    fn it_decrypts_a_keystore() {
+        let dir = std::env::temp_dir().join(format!("fs0x-keystore-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let (wallet, name) = LocalWallet::new_keystore(&dir, &mut thread_rng(), "password", None).unwrap();
+        let signer = DefaultEthereumSigner::from_keystore(&dir.join(&name), "password").unwrap();
+        let wrong_password = DefaultEthereumSigner::from_keystore(&dir.join(name), "wrong");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(signer.get_address(), format!("0x{}", hex::encode(wallet.address().as_bytes())));
+        assert!(wrong_password.is_err());
+    }
+}
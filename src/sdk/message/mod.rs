@@ -0,0 +1,22 @@
+use crate::http::HttpClient;
+
+use self::v0::*;
+
+pub mod v0;
+
+#[derive(Clone)]
+pub struct MessageSDK {
+    v0: MessageSDKV0,
+}
+
+impl MessageSDK {
+    pub fn new(client: HttpClient) -> Self {
+        MessageSDK {
+            v0: MessageSDKV0::new(client),
+        }
+    }
+
+    pub fn v0(&self) -> &MessageSDKV0 {
+        &self.v0
+    }
+}
@@ -0,0 +1,65 @@
+pub mod res_objects;
+
+use std::collections::HashMap;
+
+use reqwest::Method;
+use thiserror::Error;
+
+use crate::http::{HttpClient, HttpClientError, Request};
+use self::res_objects::{MessageStatus, MessageStatusResponse};
+
+#[derive(Debug, Error)]
+pub enum MessageSDKV0Error {
+    #[error("http client encountered an error: {0}")]
+    Client(#[from] HttpClientError),
+
+    #[error("failed to deserialize response: {0}")]
+    ResponseDeserializationError(#[from] reqwest::Error),
+}
+
+#[derive(Clone)]
+pub struct MessageSDKV0 {
+    client: HttpClient,
+}
+
+impl MessageSDKV0 {
+    pub fn new(client: HttpClient) -> Self {
+        MessageSDKV0 { client }
+    }
+
+    /// Get the processing state of a message.
+    ///
+    /// Messages broadcast but not received by the node yet are not found.
+    pub async fn status(&self, item_hash: &str) -> Result<MessageStatus, MessageSDKV0Error> {
+        let req = Request {
+            method: Method::GET,
+            path: format!("/api/v0/messages/{item_hash}/status"),
+            query_params: HashMap::new(),
+        };
+
+        let res = self.client.do_request(req).await?;
+
+        let data = res
+            .json::<MessageStatusResponse>()
+            .await
+            .map_err(MessageSDKV0Error::ResponseDeserializationError)?;
+
+        Ok(data.status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_deserializes_a_message_status() {
+        let status: MessageStatusResponse = serde_json::from_str(r#"{
+            "item_hash": "73475cb40a568e8da8a045ced110137e159f890ac4da883b6b17dc651b3a8049",
+            "status": "processed",
+            "reception_time": "2024-03-01T10:00:00Z"
+        }"#).unwrap();
+
+        assert_eq!(status.status, MessageStatus::Processed);
+    }
+}
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+/// The processing state of a message on the Aleph node.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageStatus {
+    Pending,
+    Processed,
+    Rejected,
+    Forgotten,
+    Removing,
+    Removed,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageStatusResponse {
+    /// The message's hash.
+    pub item_hash: String,
+    /// The processing state of the message.
+    pub status: MessageStatus,
+}
@@ -1,25 +1,46 @@
 use crate::http::HttpClient;
 
-use self::{post::PostSDK, store::StoreSDK};
+use self::{account::AccountSDK, message::MessageSDK, post::PostSDK, store::StoreSDK};
 
+pub mod account;
+pub mod message;
 pub mod post;
 pub mod store;
 pub mod common;
 
+#[derive(Clone)]
 pub struct AlephSDK {
+    account: AccountSDK,
+    message: MessageSDK,
     post: PostSDK,
+    store: StoreSDK,
 }
 
 impl AlephSDK {
     pub fn new(client: HttpClient) -> Self {
         AlephSDK {
-            post: PostSDK::new(client),
+            account: AccountSDK::new(client.clone()),
+            message: MessageSDK::new(client.clone()),
+            post: PostSDK::new(client.clone()),
+            store: StoreSDK::new(client),
         }
     }
 
+    pub fn account(&self) -> &AccountSDK {
+        &self.account
+    }
+
+    pub fn message(&self) -> &MessageSDK {
+        &self.message
+    }
+
     pub fn post(&self) -> &PostSDK {
         &self.post
     }
+
+    pub fn store(&self) -> &StoreSDK {
+        &self.store
+    }
 }
 
 #[cfg(test)]
@@ -32,4 +53,4 @@ mod tests {
         let client = HttpClient::new().unwrap();
         let _sdk = AlephSDK::new(client);
     }
-}
\ No newline at end of file
+}
@@ -4,6 +4,7 @@ use self::v0::*;
 
 pub mod v0;
 
+#[derive(Clone)]
 pub struct PostSDK {
     v0: PostSDKV0,
 }
@@ -1,15 +1,13 @@
 pub mod req_objects;
 pub mod res_objects;
 
-use std::{collections::HashMap, fmt::Debug, str::FromStr, time::{SystemTime, UNIX_EPOCH}};
+use std::{collections::HashMap, fmt::Debug, time::{SystemTime, UNIX_EPOCH}};
 
-use alloy_primitives::Address;
 use reqwest::Method;
 use serde::Serialize;
 use thiserror::Error;
-use sha2::{Digest, Sha256};
 
-use crate::{http::{HttpClient, HttpClientError, Request}, sdk::{common::{BaseMessage, ItemType, MessageChain, MessageSigner, MessageSignerError, MessageType, PostContent}, post::v0::res_objects::ListPostsResponse}};
+use crate::{http::{HttpClient, HttpClientError, Request}, sdk::{common::{get_checksum_address, sign_and_broadcast, BaseMessage, BroadcastError, ItemType, MessageSignerError, MessageType, PostContent}, post::v0::res_objects::{AddJsonResponse, ListPostsResponse}}};
 use self::req_objects::{CreatePostRequest, ListPostsRequest};
 
 #[derive(Debug, Error)]
@@ -22,8 +20,12 @@ pub enum PostSDKV0Error {
 
     #[error("failed to sign message: {0}")]
     MessageSignError(#[from] MessageSignerError),
+
+    #[error("failed to broadcast message: {0}")]
+    Broadcast(#[from] BroadcastError),
 }
 
+#[derive(Clone)]
 pub struct PostSDKV0 {
     client: HttpClient,
 }
@@ -33,7 +35,7 @@ impl PostSDKV0 {
         PostSDKV0 { client }
     }
 
-    pub async fn list<T: serde::de::DeserializeOwned + Debug + Clone>(&self, params: ListPostsRequest) -> Result<ListPostsResponse, PostSDKV0Error> {
+    pub async fn list(&self, params: ListPostsRequest) -> Result<ListPostsResponse, PostSDKV0Error> {
         let query_params = params.query_params();
         
         let req = Request {
@@ -52,10 +54,13 @@ impl PostSDKV0 {
         Ok(data)
     }
 
-    pub async fn create<T: Serialize + Clone + Debug>(&self, params: &CreatePostRequest<T>) -> Result<(), PostSDKV0Error> {
+    /// Publishes a signed POST message.
+    ///
+    /// Returns the hash of the POST message.
+    pub async fn create<T: Serialize + Clone + Debug>(&self, params: &CreatePostRequest<'_, T>) -> Result<String, PostSDKV0Error> {
         let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards ???").as_secs();
 
-        let addr = Address::from_str(params.signer.get_address().as_str()).expect("parse l'addresse non ???").to_checksum(None);
+        let addr = get_checksum_address(params.signer)?;
 
         let post_content = PostContent {
             custom_type: params.custom_type.clone(),
@@ -64,92 +69,94 @@ impl PostSDKV0 {
             time: timestamp as f64,
         };
 
-        let mut message = BaseMessage {
-            channel: params.channel.clone(),
-            time: timestamp as f64,
-            message_type: MessageType::Post,
-            chain: MessageChain::Ethereum,
-            sender: addr.clone(),
-            hash_type: "sha256".to_string(),
-            item_hash: "".to_string(),
-            item_type: params.item_type,
-            content: post_content,
-            item_content: None,
-            signature: "".to_string(),
-        };
+        let mut message = BaseMessage::new(
+            params.channel.clone(),
+            MessageType::Post,
+            addr,
+            timestamp as f64,
+            params.item_type,
+            post_content,
+        );
 
         let content_json = serde_json::to_string(&message.content).unwrap();
 
-        match content_json.bytes().len() < 50_000 && message.item_type == ItemType::Inline {
+        match content_json.len() < 50_000 && message.item_type == ItemType::Inline {
             true => {
-                message.item_type = ItemType::Inline;
-                
-                let mut hasher = Sha256::new();
-                hasher.update(content_json.as_bytes());
-
-                message.item_hash = format!("{:x}", hasher.finalize());           
-                message.item_content = Some(content_json);
+                message.set_inline_content(content_json);
             },
             false => {
-                message.item_type = ItemType::Storage;
-
                 let req = Request {
                     method: Method::POST,
                     path: "/api/v0/storage/add_json".to_string(),
                     query_params: HashMap::new(),
                 };
 
-                let res = self.client.do_post_request(req, content_json).await?;
+                let res = self.client.do_post_request(req, &message.content).await?;
                 let data = res
-                    .text()
+                    .json::<AddJsonResponse>()
                     .await
                     .map_err(PostSDKV0Error::ResponseDeserializationError)?;
 
-                println!("Storage response: {}", data);
+                message.set_stored_content(data.hash);
             }
         }
 
-        message.signature = params.signer.sign(&message).await?;
-
-        let body = PubSubNotifyRequest {
-            topic: "ALEPH-TEST".to_string(),
-            data: serde_json::to_string(&message).unwrap(),
-        };
-
-        let req = Request {
-            method: Method::POST,
-            path: "/api/v0/ipfs/pubsub/pub".to_string(),
-            query_params: HashMap::new(),
-        };
-
-        let res = self.client.do_post_request(req, body).await?;
-
-        let data = res
-            .text()
-            .await
-            .map_err(PostSDKV0Error::ResponseDeserializationError)?;
+        sign_and_broadcast(&self.client, params.signer, &mut message).await?;
 
-        Ok(())
+        Ok(message.item_hash)
     }
 }
 
-#[derive(Debug, Serialize)]
-pub struct PubSubNotifyRequest {
-    pub topic: String,
-    pub data: String,
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{http::HttpClient, sdk::common::DefaultEthereumSigner};
-    use serde::Deserialize;
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener};
+
+    /// Serves a node answering every request with the given JSON body,
+    /// returning its url and the paths requested so far.
+    async fn serve_json(body: &'static str) -> (String, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let paths = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let requested = paths.clone();
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buffer = [0; 8192];
+
+                // Read the headers, then the body announced by Content-Length.
+                let (head, length) = loop {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let length = text.lines()
+                            .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|value| value.trim().parse::<usize>().unwrap()))
+                            .unwrap_or(0);
+                        break (end + 4, length);
+                    }
+                };
+                while request.len() < head + length {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                }
+
+                let path = String::from_utf8_lossy(&request).split(' ').nth(1).unwrap().to_string();
+                requested.lock().unwrap().push(path);
+
+                let response = format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
 
-    #[derive(Debug, Deserialize, Serialize, Clone, Copy)]
-    #[serde(tag = "metrics")]
-    struct Metrics {}
+        (url, paths)
+    }
 
     #[tokio::test]
+    #[ignore = "calls the live Aleph API"]
     async fn it_lists_posts() {
         let client = HttpClient::new().unwrap();
         let sdk = PostSDKV0::new(client);
@@ -157,7 +164,7 @@ mod tests {
         let params = ListPostsRequest::default().with_hashes(vec![
             "b33110b8c8e9d8d6dc67813007c5b8318ed3720776c4ee6431cc60ee4b0d18ad".to_string(),
         ]);
-        let _posts = sdk.list::<Metrics>(params)
+        let _posts = sdk.list(params)
             .await
             .unwrap();
     }
@@ -168,13 +175,14 @@ mod tests {
     }
 
     #[tokio::test]
+    #[ignore = "calls the live Aleph API"]
     async fn it_creates_post() {
         let client = HttpClient::new().unwrap();
         let sdk = PostSDKV0::new(client);
 
         let signer = DefaultEthereumSigner::new("0xdcf2cbdd171a21c480aa7f53d77f31bb102282b3ff099c78e3118b37348c72f7".to_string()).unwrap();
         let params = CreatePostRequest {
-            signer,
+            signer: &signer,
             custom_type: "fs0x-test".to_string(),
             content: TestPostMessage {
                 content: 42,
@@ -187,4 +195,23 @@ mod tests {
             .await
             .unwrap();
     }
+
+    #[tokio::test]
+    async fn it_stores_large_posts_and_returns_their_hash() {
+        let hash = "9b5c2ddcbd8ebd1b8ccabdbcad7bc98e27ac0d88e28ab8a4d05e56bb6fa0a1d5";
+        let (url, paths) = serve_json(r#"{"status": "success", "hash": "9b5c2ddcbd8ebd1b8ccabdbcad7bc98e27ac0d88e28ab8a4d05e56bb6fa0a1d5"}"#).await;
+        let sdk = PostSDKV0::new(HttpClient::new().unwrap().with_api_url(&url).unwrap());
+
+        let signer = DefaultEthereumSigner::random();
+        let params = CreatePostRequest {
+            signer: &signer,
+            custom_type: "fs0x-test".to_string(),
+            content: "a".repeat(60_000),
+            channel: "fsx".to_string(),
+            item_type: ItemType::Inline,
+        };
+
+        assert_eq!(sdk.create(&params).await.unwrap(), hash);
+        assert_eq!(*paths.lock().unwrap(), vec!["/api/v0/storage/add_json", "/api/v0/ipfs/pubsub/pub"]);
+    }
 }
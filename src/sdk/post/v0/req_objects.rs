@@ -1,8 +1,6 @@
 use std::collections::HashMap;
 
-use serde::Serialize;
-
-use crate::sdk::common::{BaseMessage, DefaultEthereumSigner, ItemType, MessageSigner};
+use crate::sdk::common::{DefaultEthereumSigner, ItemType};
 
 pub struct ListPostsRequest {
     pub pagination: u32,
@@ -48,11 +46,13 @@ impl ListPostsRequest {
         self
     }
 
+    #[allow(dead_code)]
     pub fn with_references(mut self, references: Vec<String>) -> Self {
         self.references = Some(references);
         self
     }
 
+    #[allow(dead_code)]
     pub fn with_addresses(mut self, addresses: Vec<String>) -> Self {
         self.addresses = Some(addresses);
         self
@@ -117,8 +117,8 @@ impl ListPostsRequest {
     }
 }
 
-pub struct CreatePostRequest<T> {
-    pub signer: DefaultEthereumSigner,
+pub struct CreatePostRequest<'a, T> {
+    pub signer: &'a DefaultEthereumSigner,
     pub custom_type: String,
     pub content: T,
     pub channel: String,
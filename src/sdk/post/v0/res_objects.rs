@@ -2,7 +2,7 @@ use std::fmt::Debug;
 
 use serde::{Deserialize, Serialize};
 
-use crate::sdk::common::{ItemType, MessageChain};
+use crate::sdk::common::{verify_signature, ItemType, MessageChain, MessageSignerError, MessageType};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PostMessage<T: Debug + Clone> {
@@ -27,6 +27,25 @@ pub struct PostMessage<T: Debug + Clone> {
     pub size: u64,
     /// Was the message confirmed?
     pub confirmed: bool,
+
+    /// The sender's signature of the message.
+    #[serde(default)]
+    pub signature: String,
+}
+
+impl<T: Debug + Clone> PostMessage<T> {
+    /// Checks that the post was signed by its sender.
+    pub fn verify(&self) -> Result<(), MessageSignerError> {
+        verify_signature(self.chain, &self.sender, MessageType::Post, &self.item_hash, &self.signature)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddJsonResponse {
+    /// The status of the upload.
+    pub status: String,
+    /// The hash of the stored content.
+    pub hash: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -4,12 +4,13 @@ use self::v0::StoreSDKV0;
 
 pub mod v0;
 
-pub struct StoreSDK<'a> {
-    v0: StoreSDKV0<'a>,
+#[derive(Clone)]
+pub struct StoreSDK {
+    v0: StoreSDKV0,
 }
 
-impl <'a>StoreSDK<'a> {
-    pub fn new(client: &'a HttpClient) -> Self {
+impl StoreSDK {
+    pub fn new(client: HttpClient) -> Self {
         StoreSDK {
             v0: StoreSDKV0::new(client),
         }
@@ -18,4 +19,4 @@ impl <'a>StoreSDK<'a> {
     pub fn v0(&self) -> &StoreSDKV0 {
         &self.v0
     }
-}
\ No newline at end of file
+}
@@ -1,11 +1,278 @@
-use crate::http::HttpClient;
+pub mod req_objects;
+pub mod res_objects;
 
-pub struct StoreSDKV0<'a> {
-    client: &'a HttpClient,
+use std::{collections::HashMap, time::{SystemTime, UNIX_EPOCH}};
+
+use reqwest::{multipart::{Form, Part}, Method};
+use thiserror::Error;
+
+use crate::{http::{HttpClient, HttpClientError, Request}, sdk::{common::{get_checksum_address, sign_and_broadcast, BaseMessage, BroadcastError, ItemType, MessageSignerError, MessageType, StoreContent}, store::v0::res_objects::{ListStoresResponse, UploadFileResponse}}};
+use self::req_objects::{CreateStoreRequest, ListStoresRequest};
+
+#[derive(Debug, Error)]
+pub enum StoreSDKV0Error {
+    #[error("http client encountered an error: {0}")]
+    Client(#[from] HttpClientError),
+
+    #[error("failed to deserialize response: {0}")]
+    ResponseDeserializationError(#[from] reqwest::Error),
+
+    #[error("failed to sign message: {0}")]
+    MessageSignError(#[from] MessageSignerError),
+
+    #[error("failed to broadcast message: {0}")]
+    Broadcast(#[from] BroadcastError),
+}
+
+#[derive(Clone)]
+pub struct StoreSDKV0 {
+    client: HttpClient,
 }
 
-impl <'a>StoreSDKV0<'a> {
-    pub fn new(client: &'a HttpClient) -> Self {
+impl StoreSDKV0 {
+    pub fn new(client: HttpClient) -> Self {
         StoreSDKV0 { client }
     }
-}
\ No newline at end of file
+
+    /// Uploads raw bytes to the Aleph storage engine and returns their hash.
+    pub async fn upload(&self, data: Vec<u8>) -> Result<String, StoreSDKV0Error> {
+        self.upload_file("/api/v0/storage/add_file", data).await
+    }
+
+    /// Uploads raw bytes to IPFS through the Aleph node and returns their CID.
+    #[allow(dead_code)]
+    pub async fn upload_to_ipfs(&self, data: Vec<u8>) -> Result<String, StoreSDKV0Error> {
+        self.upload_file("/api/v0/ipfs/add_file", data).await
+    }
+
+    async fn upload_file(&self, path: &str, data: Vec<u8>) -> Result<String, StoreSDKV0Error> {
+        let req = Request {
+            method: Method::POST,
+            path: path.to_string(),
+            query_params: HashMap::new(),
+        };
+
+        let form = Form::new().part("file", Part::bytes(data).file_name("file"));
+        let res = self.client.do_multipart_request(req, form).await?;
+
+        let data = res
+            .json::<UploadFileResponse>()
+            .await
+            .map_err(StoreSDKV0Error::ResponseDeserializationError)?;
+
+        Ok(data.hash)
+    }
+
+    /// Publishes a signed STORE message pointing at previously uploaded content.
+    ///
+    /// Returns the hash of the STORE message.
+    pub async fn create(&self, params: &CreateStoreRequest<'_>) -> Result<String, StoreSDKV0Error> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards ???").as_secs();
+
+        let addr = get_checksum_address(params.signer)?;
+
+        let store_content = StoreContent {
+            address: addr.clone(),
+            item_type: params.item_type,
+            item_hash: params.item_hash.clone(),
+            time: timestamp as f64,
+        };
+        let content_json = serde_json::to_string(&store_content).unwrap();
+
+        let mut message = BaseMessage::new(
+            params.channel.clone(),
+            MessageType::Store,
+            addr,
+            timestamp as f64,
+            ItemType::Inline,
+            store_content,
+        );
+        message.set_inline_content(content_json);
+
+        sign_and_broadcast(&self.client, params.signer, &mut message).await?;
+
+        Ok(message.item_hash)
+    }
+
+    /// Lists the STORE messages matching the given filters.
+    #[allow(dead_code)]
+    pub async fn list(&self, params: ListStoresRequest) -> Result<ListStoresResponse, StoreSDKV0Error> {
+        let req = Request {
+            method: Method::GET,
+            path: "/api/v0/messages.json".to_string(),
+            query_params: params.query_params(),
+        };
+
+        let res = self.client.do_request(req).await?;
+
+        let data = res
+            .json::<ListStoresResponse>()
+            .await
+            .map_err(StoreSDKV0Error::ResponseDeserializationError)?;
+
+        Ok(data)
+    }
+
+    /// Downloads the raw bytes stored under the given hash.
+    ///
+    /// The node resolves the storage engine from the hash format, so both
+    /// the hashes returned by `upload` and the CIDs returned by
+    /// `upload_to_ipfs` can be downloaded.
+    pub async fn download(&self, hash: &str) -> Result<Vec<u8>, StoreSDKV0Error> {
+        let req = Request {
+            method: Method::GET,
+            path: format!("/api/v0/storage/raw/{}", hash),
+            query_params: HashMap::new(),
+        };
+
+        let res = self.client.do_request(req).await?;
+
+        let data = res
+            .bytes()
+            .await
+            .map_err(StoreSDKV0Error::ResponseDeserializationError)?;
+
+        Ok(data.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdk::common::DefaultEthereumSigner;
+
+    #[tokio::test]
+    #[ignore = "calls the live Aleph API"]
+    async fn it_uploads_and_downloads_content() {
+        let client = HttpClient::new().unwrap();
+        let sdk = StoreSDKV0::new(client);
+
+        let hash = sdk.upload("fs0x".as_bytes().to_vec())
+            .await
+            .unwrap();
+        let data = sdk.download(&hash)
+            .await
+            .unwrap();
+
+        assert_eq!(data, "fs0x".as_bytes());
+    }
+
+    #[tokio::test]
+    #[ignore = "calls the live Aleph API"]
+    async fn it_uploads_to_ipfs_and_downloads_content() {
+        let client = HttpClient::new().unwrap();
+        let sdk = StoreSDKV0::new(client);
+
+        let cid = sdk.upload_to_ipfs("fs0x".as_bytes().to_vec())
+            .await
+            .unwrap();
+        let data = sdk.download(&cid)
+            .await
+            .unwrap();
+
+        assert!(cid.starts_with("Qm") || cid.starts_with("bafy"));
+        assert_eq!(data, "fs0x".as_bytes());
+    }
+
+    #[tokio::test]
+    #[ignore = "calls the live Aleph API"]
+    async fn it_creates_and_lists_store_message() {
+        let client = HttpClient::new().unwrap();
+        let sdk = StoreSDKV0::new(client);
+
+        let hash = sdk.upload("fs0x".as_bytes().to_vec())
+            .await
+            .unwrap();
+
+        let signer = DefaultEthereumSigner::new("0xdcf2cbdd171a21c480aa7f53d77f31bb102282b3ff099c78e3118b37348c72f7".to_string()).unwrap();
+        let params = CreateStoreRequest {
+            signer: &signer,
+            channel: "fsx".to_string(),
+            item_hash: hash.clone(),
+            item_type: ItemType::Storage,
+        };
+
+        let message_hash = sdk.create(&params)
+            .await
+            .unwrap();
+
+        // The message is broadcasted through pubsub, give the node some time
+        // to process it before looking it up.
+        let mut messages = vec![];
+        for _ in 0..10 {
+            let params = ListStoresRequest::default()
+                .with_hashes(vec![message_hash.clone()]);
+            messages = sdk.list(params)
+                .await
+                .unwrap()
+                .messages;
+
+            if !messages.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+        }
+
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].item_hash, message_hash);
+        assert_eq!(messages[0].content.item_hash, hash);
+        assert_eq!(messages[0].content.item_type, ItemType::Storage);
+    }
+
+    #[test]
+    fn it_serializes_the_store_content() {
+        let content = StoreContent {
+            address: "0x0".to_string(),
+            item_type: ItemType::Storage,
+            item_hash: "hash".to_string(),
+            time: 1.0,
+        };
+
+        let json = serde_json::to_value(&content).unwrap();
+
+        assert_eq!(json, serde_json::json!({
+            "address": "0x0",
+            "item_type": "storage",
+            "item_hash": "hash",
+            "time": 1.0,
+        }));
+
+        let content = StoreContent {
+            item_type: ItemType::Ipfs,
+            ..content
+        };
+
+        assert_eq!(serde_json::to_value(&content).unwrap()["item_type"], "ipfs");
+    }
+
+    #[test]
+    fn it_deserializes_the_list_stores_response() {
+        let json = r#"{
+            "messages": [{
+                "channel": "fsx",
+                "time": 1.0,
+                "chain": "ETH",
+                "sender": "0x0",
+                "type": "STORE",
+                "item_hash": "message_hash",
+                "item_type": "inline",
+                "content": {
+                    "address": "0x0",
+                    "item_type": "storage",
+                    "item_hash": "hash",
+                    "time": 1.0
+                },
+                "confirmed": true
+            }],
+            "pagination_page": 1,
+            "pagination_total": 1
+        }"#;
+
+        let res: ListStoresResponse = serde_json::from_str(json).unwrap();
+
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].item_hash, "message_hash");
+        assert_eq!(res.messages[0].content.item_hash, "hash");
+        assert!(res.messages[0].confirmed);
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use crate::sdk::common::{DefaultEthereumSigner, ItemType};
+
+#[allow(dead_code)]
+pub struct ListStoresRequest {
+    pub pagination: u32,
+    pub page: u32,
+    pub addresses: Option<Vec<String>>,
+    pub hashes: Option<Vec<String>>,
+    pub channels: Option<Vec<String>>,
+    pub start_date: Option<i64>,
+    pub end_date: Option<i64>,
+}
+
+impl Default for ListStoresRequest {
+    fn default() -> Self {
+        ListStoresRequest {
+            pagination: 200,
+            page: 1,
+            addresses: None,
+            hashes: None,
+            channels: None,
+            start_date: None,
+            end_date: None,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl ListStoresRequest {
+    pub fn with_pagination(mut self, pagination: u32) -> Self {
+        self.pagination = pagination;
+        self
+    }
+
+    pub fn with_page(mut self, page: u32) -> Self {
+        self.page = page;
+        self
+    }
+
+    pub fn with_addresses(mut self, addresses: Vec<String>) -> Self {
+        self.addresses = Some(addresses);
+        self
+    }
+
+    pub fn with_hashes(mut self, hashes: Vec<String>) -> Self {
+        self.hashes = Some(hashes);
+        self
+    }
+
+    pub fn with_channels(mut self, channels: Vec<String>) -> Self {
+        self.channels = Some(channels);
+        self
+    }
+
+    pub fn with_start_date(mut self, start_date: i64) -> Self {
+        self.start_date = Some(start_date);
+        self
+    }
+
+    pub fn with_end_date(mut self, end_date: i64) -> Self {
+        self.end_date = Some(end_date);
+        self
+    }
+
+    pub fn query_params(self) -> HashMap<String, String> {
+        let mut query_params = HashMap::new();
+
+        query_params.insert("msgTypes".to_string(), "STORE".to_string());
+        query_params.insert("pagination".to_string(), self.pagination.to_string());
+        query_params.insert("page".to_string(), self.page.to_string());
+
+        if let Some(addresses) = self.addresses {
+            query_params.insert("addresses".to_string(), addresses.join(","));
+        }
+
+        if let Some(hashes) = self.hashes {
+            query_params.insert("hashes".to_string(), hashes.join(","));
+        }
+
+        if let Some(channels) = self.channels {
+            query_params.insert("channels".to_string(), channels.join(","));
+        }
+
+        if let Some(start_date) = self.start_date {
+            query_params.insert("startDate".to_string(), start_date.to_string());
+        }
+
+        if let Some(end_date) = self.end_date {
+            query_params.insert("endDate".to_string(), end_date.to_string());
+        }
+
+        query_params
+    }
+}
+
+pub struct CreateStoreRequest<'a> {
+    pub signer: &'a DefaultEthereumSigner,
+    pub channel: String,
+    /// The hash of the content previously uploaded to the storage engine.
+    pub item_hash: String,
+    /// The storage engine the content was uploaded to.
+    pub item_type: ItemType,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_builds_the_list_stores_request_using_default_values() {
+        let request = ListStoresRequest::default();
+
+        assert_eq!(request.pagination, 200);
+        assert_eq!(request.page, 1);
+        assert!(request.addresses.is_none());
+        assert!(request.hashes.is_none());
+        assert!(request.channels.is_none());
+        assert!(request.start_date.is_none());
+        assert!(request.end_date.is_none());
+    }
+
+    #[test]
+    fn it_builds_the_list_stores_request_query_params() {
+        let request = ListStoresRequest::default()
+            .with_pagination(100)
+            .with_page(2)
+            .with_addresses(vec!["address".to_string()])
+            .with_hashes(vec!["hash".to_string()])
+            .with_channels(vec!["channel".to_string()])
+            .with_start_date(0)
+            .with_end_date(1);
+
+        let query_params = request.query_params();
+
+        assert_eq!(query_params.get("msgTypes").unwrap(), "STORE");
+        assert_eq!(query_params.get("pagination").unwrap(), "100");
+        assert_eq!(query_params.get("page").unwrap(), "2");
+        assert_eq!(query_params.get("addresses").unwrap(), "address");
+        assert_eq!(query_params.get("hashes").unwrap(), "hash");
+        assert_eq!(query_params.get("channels").unwrap(), "channel");
+        assert_eq!(query_params.get("startDate").unwrap(), "0");
+        assert_eq!(query_params.get("endDate").unwrap(), "1");
+    }
+}
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+use crate::sdk::common::{ItemType, MessageChain, StoreContent};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadFileResponse {
+    /// The status of the upload.
+    pub status: String,
+    /// The hash of the uploaded content.
+    pub hash: String,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoreMessage {
+    /// The channel where the message was received.
+    pub channel: Option<String>,
+    /// The time the message was received.
+    pub time: f64,
+
+    /// The sender's address chain.
+    pub chain: MessageChain,
+    /// The sender's public address.
+    pub sender: String,
+
+    /// The message's hash.
+    pub item_hash: String,
+    /// The message's storage type.
+    pub item_type: ItemType,
+    /// The message's content, pointing at the stored content.
+    pub content: StoreContent,
+
+    /// Was the message confirmed?
+    #[serde(default)]
+    pub confirmed: bool,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListStoresResponse {
+    /// The list of STORE messages.
+    pub messages: Vec<StoreMessage>,
+}